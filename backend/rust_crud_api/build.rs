@@ -0,0 +1,16 @@
+use std::{env, path::PathBuf};
+
+// Compiles the gRPC service definitions with a vendored protoc, so building
+// needs no system protobuf install. The descriptor set feeds reflection.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    env::set_var("PROTOC_INCLUDE", protoc_bin_vendored::include_path()?);
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    tonic_build::configure()
+        .build_client(false)
+        .file_descriptor_set_path(out_dir.join("employee_descriptor.bin"))
+        .compile(&["proto/employee.proto"], &["proto"])?;
+
+    Ok(())
+}
@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE api_key (
+    id  SERIAL PRIMARY KEY,
+    name varchar(255) NOT NULL,
+    prefix varchar(16) NOT NULL,
+    key_hash char(64) NOT NULL UNIQUE,
+    scopes text[] NOT NULL DEFAULT '{}',
+    expires_at timestamptz,
+    revoked_at timestamptz,
+    last_used_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
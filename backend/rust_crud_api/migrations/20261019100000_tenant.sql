@@ -0,0 +1,24 @@
+-- Add migration script here
+CREATE TABLE tenant (
+    id  SERIAL PRIMARY KEY,
+    slug varchar(63) NOT NULL UNIQUE,
+    name varchar(255) NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+INSERT INTO tenant (slug, name) VALUES ('default', 'Default');
+
+ALTER TABLE employee ADD COLUMN tenant_id integer REFERENCES tenant(id);
+UPDATE employee SET tenant_id = (SELECT id FROM tenant WHERE slug = 'default');
+ALTER TABLE employee ALTER COLUMN tenant_id SET NOT NULL;
+CREATE INDEX employee_tenant_id_idx ON employee (tenant_id);
+
+-- Keys bound to a tenant can only act on that tenant; NULL means any tenant.
+ALTER TABLE api_key ADD COLUMN tenant_id integer REFERENCES tenant(id);
+
+-- Row-level security backs up the tenant filters in the application. It only
+-- applies to roles that do not own the table, and requires TENANT_RLS=true so
+-- the server sets app.tenant_id on every transaction.
+ALTER TABLE employee ENABLE ROW LEVEL SECURITY;
+CREATE POLICY employee_tenant_isolation ON employee
+    USING (tenant_id = current_setting('app.tenant_id', true)::integer);
@@ -0,0 +1,10 @@
+-- Add migration script here
+ALTER TABLE employee ALTER COLUMN eemail TYPE text;
+ALTER TABLE employee ALTER COLUMN econtact TYPE text;
+
+-- HMACs of the normalised plaintext, used for equality lookups on the
+-- encrypted columns.
+ALTER TABLE employee ADD COLUMN eemail_bidx char(64);
+ALTER TABLE employee ADD COLUMN econtact_bidx char(64);
+CREATE INDEX employee_eemail_bidx_idx ON employee (tenant_id, eemail_bidx);
+CREATE INDEX employee_econtact_bidx_idx ON employee (tenant_id, econtact_bidx);
@@ -0,0 +1,13 @@
+-- Add migration script here
+ALTER TABLE employee ADD COLUMN erased_at timestamptz;
+
+CREATE TABLE erasure_request (
+    id  SERIAL PRIMARY KEY,
+    employee_id integer NOT NULL,
+    tenant_id integer NOT NULL REFERENCES tenant(id),
+    reason text NOT NULL,
+    requested_by varchar(255) NOT NULL,
+    requested_at timestamptz NOT NULL DEFAULT now(),
+    completed_at timestamptz
+);
+CREATE INDEX erasure_request_employee_id_idx ON erasure_request (tenant_id, employee_id);
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE idempotency_key (
+    tenant_id integer NOT NULL REFERENCES tenant(id),
+    key varchar(255) NOT NULL,
+    fingerprint char(64) NOT NULL,
+    status smallint,
+    content_type varchar(255),
+    body bytea,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (tenant_id, key)
+);
@@ -0,0 +1,18 @@
+-- Add migration script here
+ALTER TABLE employee ADD COLUMN status text NOT NULL DEFAULT 'active'
+    CHECK (status IN ('onboarding', 'active', 'on_leave', 'terminated'));
+ALTER TABLE employee ALTER COLUMN status SET DEFAULT 'onboarding';
+CREATE INDEX employee_status_idx ON employee (tenant_id, status);
+
+CREATE TABLE employee_status_transition (
+    id  SERIAL PRIMARY KEY,
+    employee_id integer NOT NULL,
+    tenant_id integer NOT NULL REFERENCES tenant(id),
+    from_status text NOT NULL,
+    to_status text NOT NULL,
+    reason text NOT NULL,
+    effective_date date NOT NULL,
+    recorded_by varchar(255),
+    recorded_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX employee_status_transition_employee_id_idx ON employee_status_transition (tenant_id, employee_id);
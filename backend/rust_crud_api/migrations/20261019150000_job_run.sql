@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE job_run (
+    id  BIGSERIAL PRIMARY KEY,
+    job varchar(63) NOT NULL,
+    status text NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'running', 'succeeded', 'failed')),
+    run_at timestamptz NOT NULL,
+    -- Name of the API key that triggered the run; NULL for scheduled runs.
+    triggered_by varchar(255),
+    started_at timestamptz,
+    finished_at timestamptz,
+    error text,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- Every instance enqueues the same scheduled runs; this keeps one of each.
+CREATE UNIQUE INDEX job_run_scheduled_idx ON job_run (job, run_at) WHERE triggered_by IS NULL;
+CREATE INDEX job_run_queued_idx ON job_run (run_at) WHERE status = 'queued';
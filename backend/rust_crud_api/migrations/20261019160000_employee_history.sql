@@ -0,0 +1,42 @@
+-- Add migration script here
+-- Every version of every employee row, with the period it was current for.
+-- Rows are kept as JSON so the history survives columns being added later.
+CREATE TABLE employee_history (
+    history_id  BIGSERIAL PRIMARY KEY,
+    employee_id integer NOT NULL,
+    tenant_id integer NOT NULL REFERENCES tenant(id),
+    data jsonb NOT NULL,
+    valid_from timestamptz NOT NULL,
+    -- NULL while the version is current.
+    valid_to timestamptz
+);
+CREATE INDEX employee_history_employee_id_idx ON employee_history (tenant_id, employee_id, valid_from);
+CREATE INDEX employee_history_valid_idx ON employee_history (tenant_id, valid_from, valid_to);
+
+CREATE FUNCTION employee_versioning() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE employee_history SET valid_to = now()
+            WHERE employee_id = OLD.id AND tenant_id = OLD.tenant_id AND valid_to IS NULL;
+        -- A row changed more than once in a transaction keeps its last version.
+        DELETE FROM employee_history
+            WHERE employee_id = OLD.id AND tenant_id = OLD.tenant_id AND valid_from = valid_to;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        INSERT INTO employee_history (employee_id, tenant_id, data, valid_from)
+            VALUES (NEW.id, NEW.tenant_id, to_jsonb(NEW), now());
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER employee_versioning AFTER INSERT OR UPDATE OR DELETE ON employee
+    FOR EACH ROW EXECUTE FUNCTION employee_versioning();
+
+-- Existing rows are only known from now on.
+INSERT INTO employee_history (employee_id, tenant_id, data, valid_from)
+    SELECT id, tenant_id, to_jsonb(employee), now() FROM employee;
+
+ALTER TABLE employee_history ENABLE ROW LEVEL SECURITY;
+CREATE POLICY employee_history_tenant_isolation ON employee_history
+    USING (tenant_id = current_setting('app.tenant_id', true)::integer);
@@ -0,0 +1,23 @@
+-- Add migration script here
+CREATE TABLE custom_field (
+    id  SERIAL PRIMARY KEY,
+    tenant_id integer NOT NULL REFERENCES tenant(id),
+    name varchar(63) NOT NULL CHECK (name ~ '^[a-z][a-z0-9_]*$'),
+    kind text NOT NULL CHECK (kind IN ('string', 'integer', 'number', 'boolean', 'date', 'enum')),
+    required boolean NOT NULL DEFAULT false,
+    -- Strings only: a regular expression the whole value must match.
+    pattern text,
+    -- Bounds on the value, or on the length for strings.
+    min double precision,
+    max double precision,
+    -- Enums only: the allowed values.
+    options text[],
+    created_at timestamptz NOT NULL DEFAULT now(),
+    UNIQUE (tenant_id, name)
+);
+
+ALTER TABLE employee ADD COLUMN custom jsonb NOT NULL DEFAULT '{}';
+CREATE INDEX employee_custom_idx ON employee USING gin (custom jsonb_path_ops);
+
+-- Older versions predate the column.
+UPDATE employee_history SET data = data || '{"custom": {}}' WHERE NOT data ? 'custom';
@@ -0,0 +1,20 @@
+-- Add migration script here
+-- Masked emails already show the domain, so it is kept in plain text for
+-- grouping. Existing rows get it from POST /admin/pii/reencrypt.
+ALTER TABLE employee ADD COLUMN eemail_domain varchar(255);
+ALTER TABLE employee ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+CREATE INDEX employee_created_at_idx ON employee (tenant_id, created_at);
+
+-- Backfilling is not a change to the employees, so it is kept out of the
+-- history; existing versions get the creation time instead.
+ALTER TABLE employee DISABLE TRIGGER employee_versioning;
+UPDATE employee e SET created_at = first.valid_from
+    FROM (SELECT employee_id, tenant_id, min(valid_from) AS valid_from FROM employee_history GROUP BY employee_id, tenant_id) first
+    WHERE e.id = first.employee_id AND e.tenant_id = first.tenant_id;
+ALTER TABLE employee ENABLE TRIGGER employee_versioning;
+
+UPDATE employee_history h SET data = h.data || jsonb_build_object('created_at', first.valid_from, 'eemail_domain', NULL)
+    FROM (SELECT employee_id, tenant_id, min(valid_from) AS valid_from FROM employee_history GROUP BY employee_id, tenant_id) first
+    WHERE h.employee_id = first.employee_id AND h.tenant_id = first.tenant_id;
+
+CREATE INDEX employee_status_transition_effective_date_idx ON employee_status_transition (tenant_id, to_status, effective_date);
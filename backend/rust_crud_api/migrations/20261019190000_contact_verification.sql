@@ -0,0 +1,20 @@
+-- Add migration script here
+-- Cleared whenever the value changes, so a set timestamp always refers to the
+-- current address or number.
+ALTER TABLE employee ADD COLUMN eemail_verified_at timestamptz;
+ALTER TABLE employee ADD COLUMN econtact_verified_at timestamptz;
+
+CREATE TABLE contact_verification (
+    id  SERIAL PRIMARY KEY,
+    tenant_id integer NOT NULL REFERENCES tenant(id),
+    employee_id integer NOT NULL,
+    channel text NOT NULL CHECK (channel IN ('email', 'phone')),
+    code_hash char(64) NOT NULL,
+    -- Blind index of the value the code was sent to.
+    target_bidx char(64) NOT NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    expires_at timestamptz NOT NULL,
+    confirmed_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX contact_verification_employee_id_idx ON contact_verification (tenant_id, employee_id, channel);
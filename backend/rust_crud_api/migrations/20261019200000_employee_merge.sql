@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX employee_ename_trgm_idx ON employee USING gin (ename gin_trgm_ops);
+
+-- Employees folded into another record. The merged row is deleted, so its
+-- versions stay in employee_history under the old id, and lookups of that id
+-- are redirected to the survivor.
+CREATE TABLE employee_merge (
+    tenant_id integer NOT NULL REFERENCES tenant(id),
+    merged_id integer NOT NULL,
+    survivor_id integer NOT NULL,
+    merged_by varchar(255),
+    merged_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (tenant_id, merged_id)
+);
+CREATE INDEX employee_merge_survivor_id_idx ON employee_merge (tenant_id, survivor_id);
+
+ALTER TABLE employee_merge ENABLE ROW LEVEL SECURITY;
+CREATE POLICY employee_merge_tenant_isolation ON employee_merge
+    USING (tenant_id = current_setting('app.tenant_id', true)::integer);
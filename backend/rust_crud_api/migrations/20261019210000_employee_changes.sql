@@ -0,0 +1,21 @@
+-- Add migration script here
+-- Announces committed changes to employee rows on the `employee_changes`
+-- channel, for the gRPC Watch call.
+CREATE FUNCTION employee_changes() RETURNS trigger AS $$
+DECLARE
+    changed employee;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+    PERFORM pg_notify('employee_changes', json_build_object(
+        'op', lower(TG_OP), 'tenant_id', changed.tenant_id, 'id', changed.id
+    )::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER employee_changes AFTER INSERT OR UPDATE OR DELETE ON employee
+    FOR EACH ROW EXECUTE FUNCTION employee_changes();
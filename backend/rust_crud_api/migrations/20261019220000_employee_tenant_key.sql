@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Clients choose employee ids, so ids are unique per tenant rather than
+-- across tenants; a taken id must not reveal another tenant's employee.
+ALTER TABLE employee DROP CONSTRAINT employee_pkey;
+ALTER TABLE employee ADD PRIMARY KEY (tenant_id, id);
+DROP INDEX employee_tenant_id_idx;
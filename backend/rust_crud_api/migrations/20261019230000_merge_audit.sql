@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Versions and status changes of a merged employee move to the record it was
+-- merged into; merged_from keeps the id they were recorded under. Only rows
+-- with merged_from NULL describe the record itself.
+ALTER TABLE employee_history ADD COLUMN merged_from integer;
+ALTER TABLE employee_status_transition ADD COLUMN merged_from integer;
+
+UPDATE employee_history h SET employee_id = m.survivor_id, merged_from = h.employee_id
+    FROM employee_merge m WHERE m.tenant_id = h.tenant_id AND m.merged_id = h.employee_id;
+UPDATE employee_status_transition t SET employee_id = m.survivor_id, merged_from = t.employee_id
+    FROM employee_merge m WHERE m.tenant_id = t.tenant_id AND m.merged_id = t.employee_id;
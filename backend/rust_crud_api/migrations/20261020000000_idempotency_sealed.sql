@@ -0,0 +1,8 @@
+-- Add migration script here
+-- Stored responses are encrypted like the contact columns and list the
+-- employees they describe, so erasure can drop them. Responses stored so far
+-- hold contact data in plain text; they are dropped, and retries of those
+-- requests run again.
+DELETE FROM idempotency_key;
+ALTER TABLE idempotency_key ALTER COLUMN body TYPE text USING NULL;
+ALTER TABLE idempotency_key ADD COLUMN employee_ids integer[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::{request::Parts, StatusCode},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::{config::Config, errors::CustomError, logging::query_span, tenants, tokens::Claims};
+
+pub const ADMIN_SCOPE: &str = "admin";
+
+// The authenticated caller of an `X-Api-Key` request.
+#[derive(Clone, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub tenant_id: Option<i32>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiKey {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(key) = parts.extensions.get::<ApiKey>() {
+            return Ok(key.clone());
+        }
+
+        let secret = parts
+            .headers
+            .get("X-Api-Key")
+            .and_then(|value| value.to_str().ok())
+            .ok_or(CustomError::Unauthorized)?
+            .to_string();
+
+        let Extension(config) = Extension::<Arc<Config>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| CustomError::InternalServerError)?;
+        let Extension(pool) = Extension::<PgPool>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| CustomError::InternalServerError)?;
+
+        let key = match &config.admin_api_key {
+            Some(admin) if hash(admin) == hash(&secret) => ApiKey {
+                id: 0,
+                name: "bootstrap".to_string(),
+                scopes: vec![ADMIN_SCOPE.to_string()],
+                tenant_id: None,
+            },
+            _ => authenticate(&pool, &secret).await?,
+        };
+
+        tracing::debug!(api_key = key.id, name = %key.name, "authenticated api key");
+        parts.extensions.insert(key.clone());
+        Ok(key)
+    }
+}
+
+// Guards admin endpoints: the caller's key must carry the admin scope.
+pub struct Admin(pub ApiKey);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Admin {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let key = ApiKey::from_request_parts(parts, state).await?;
+        if !key.scopes.iter().any(|scope| scope == ADMIN_SCOPE) {
+            return Err(CustomError::Forbidden);
+        }
+        Ok(Admin(key))
+    }
+}
+
+// Guards endpoints that reach across tenants: the caller's admin key must not
+// be bound to a tenant.
+pub struct GlobalAdmin(pub ApiKey);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for GlobalAdmin {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Admin(key) = Admin::from_request_parts(parts, state).await?;
+        if key.tenant_id.is_some() {
+            return Err(CustomError::Forbidden);
+        }
+        Ok(GlobalAdmin(key))
+    }
+}
+
+// Guards endpoints anonymous callers may not use: the request must carry a
+// valid API key or bearer token.
+pub struct Authenticated;
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Authenticated {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if parts.headers.contains_key("X-Api-Key") {
+            ApiKey::from_request_parts(parts, state).await?;
+        } else {
+            Claims::from_request_parts(parts, state).await?;
+        }
+        Ok(Authenticated)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredKey {
+    #[sqlx(flatten)]
+    key: ApiKey,
+    // Whether last_used_at is more than a minute old.
+    stale: bool,
+}
+
+// Looks the key up without writing; last_used_at is refreshed at most once a
+// minute, so authenticating every request (the rate limiter does too) stays a
+// read.
+async fn authenticate(pool: &PgPool, secret: &str) -> Result<ApiKey, CustomError> {
+    let sql = "SELECT id, name, scopes, tenant_id, last_used_at IS NULL OR last_used_at < now() - interval '1 minute' AS stale FROM api_key \
+               WHERE key_hash=$1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+    let stored: StoredKey = sqlx::query_as(sql)
+        .bind(hash(secret))
+        .fetch_optional(pool)
+        .instrument(query_span(sql, None))
+        .await
+        .map_err(|_| CustomError::InternalServerError)?
+        .ok_or(CustomError::Unauthorized)?;
+
+    if stored.stale {
+        let sql = "UPDATE api_key SET last_used_at=now() WHERE id=$1";
+        sqlx::query(sql)
+            .bind(stored.key.id)
+            .execute(pool)
+            .instrument(query_span(sql, None))
+            .await
+            .map_err(|_| CustomError::InternalServerError)?;
+    }
+
+    Ok(stored.key)
+}
+
+fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// Returns the secret and the prefix stored alongside its hash so keys can be
+// recognised in listings without revealing them.
+fn generate() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let secret = format!("ck_{}", hex::encode(bytes));
+    let prefix = secret[..11].to_string();
+    (secret, prefix)
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct ApiKeyInfo {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub tenant_id: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct NewApiKey {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Slug of the tenant the key is restricted to.
+    pub tenant: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// Returned only on create and rotate; the secret cannot be retrieved later.
+#[derive(Serialize)]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+    pub key: String,
+}
+
+const INFO_COLUMNS: &str = "id, name, prefix, scopes, tenant_id, expires_at, revoked_at, last_used_at, created_at";
+
+// Admin keys bound to a tenant only see and manage that tenant's keys.
+pub async fn list_keys(Admin(admin): Admin, Extension(pool): Extension<PgPool>) -> Result<Json<Vec<ApiKeyInfo>>, CustomError> {
+    let sql = format!("SELECT {INFO_COLUMNS} FROM api_key WHERE $1::integer IS NULL OR tenant_id=$1 ORDER BY id");
+    let keys = sqlx::query_as(&sql)
+    .bind(admin.tenant_id)
+    .fetch_all(&pool)
+    .instrument(query_span(&sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    Ok(Json(keys))
+}
+
+// Keys created with a tenant-bound admin key are bound to the same tenant.
+pub async fn create_key(Admin(admin): Admin, Extension(pool): Extension<PgPool>, Json(data): Json<NewApiKey>) -> Result<(StatusCode, Json<IssuedApiKey>), CustomError> {
+    let tenant_id = match &data.tenant {
+        Some(slug) => Some(tenants::tenant_id(&pool, slug).await?),
+        None => admin.tenant_id,
+    };
+    if admin.tenant_id.is_some() && tenant_id != admin.tenant_id {
+        return Err(CustomError::Forbidden);
+    }
+
+    let (key, prefix) = generate();
+    let sql = format!("INSERT INTO api_key (name, prefix, key_hash, scopes, tenant_id, expires_at) values ($1, $2, $3, $4, $5, $6) RETURNING {INFO_COLUMNS}");
+    let info = sqlx::query_as(&sql)
+    .bind(&data.name)
+    .bind(&prefix)
+    .bind(hash(&key))
+    .bind(&data.scopes)
+    .bind(tenant_id)
+    .bind(data.expires_at)
+    .fetch_one(&pool)
+    .instrument(query_span(&sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    Ok((StatusCode::CREATED, Json(IssuedApiKey { info, key })))
+}
+
+pub async fn rotate_key(Admin(admin): Admin, Path(id): Path<i32>, Extension(pool): Extension<PgPool>) -> Result<Json<IssuedApiKey>, CustomError> {
+    let (key, prefix) = generate();
+    let sql = format!("UPDATE api_key SET prefix=$1, key_hash=$2, last_used_at=NULL WHERE id=$3 AND revoked_at IS NULL AND ($4::integer IS NULL OR tenant_id=$4) RETURNING {INFO_COLUMNS}");
+    let info = sqlx::query_as(&sql)
+    .bind(&prefix)
+    .bind(hash(&key))
+    .bind(id)
+    .bind(admin.tenant_id)
+    .fetch_optional(&pool)
+    .instrument(query_span(&sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?
+    .ok_or(CustomError::TaskNotFound)?;
+
+    Ok(Json(IssuedApiKey { info, key }))
+}
+
+pub async fn revoke_key(Admin(admin): Admin, Path(id): Path<i32>, Extension(pool): Extension<PgPool>) -> Result<(StatusCode, Json<Value>), CustomError> {
+    let sql = "UPDATE api_key SET revoked_at=now() WHERE id=$1 AND revoked_at IS NULL AND ($2::integer IS NULL OR tenant_id=$2)";
+    let result = sqlx::query(sql)
+    .bind(id)
+    .bind(admin.tenant_id)
+    .execute(&pool)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err(CustomError::TaskNotFound);
+    }
+
+    Ok((StatusCode::OK, Json(json!({"msg": "API Key Revoked"}))))
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use axum::{http::StatusCode, Extension, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use crate::{
+    cache::ProfileCache,
+    errors::CustomError,
+    idempotency::Subjects,
+    models::NewProfile,
+    pii::Pii,
+    tenants::Tenant,
+    views,
+};
+
+const MAX_OPERATIONS: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Create { data: NewProfile },
+    Update { id: i32, data: NewProfile },
+    Delete { id: i32 },
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    // All operations commit together or not at all.
+    #[default]
+    Atomic,
+    // Each operation commits on its own.
+    Independent,
+}
+
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    #[serde(default)]
+    pub mode: Mode,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Serialize)]
+pub struct OperationResult {
+    pub status: u16,
+    pub body: Value,
+    // The employee the operation acted on, after following merges.
+    #[serde(skip)]
+    pub employee_id: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct BatchResponse {
+    pub committed: bool,
+    pub results: Vec<OperationResult>,
+}
+
+pub async fn batch_profiles(tenant: Tenant, Extension(pii): Extension<Arc<Pii>>, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>, Json(batch): Json<BatchRequest>) -> Result<(StatusCode, Extension<Subjects>, Json<BatchResponse>), CustomError> {
+    if batch.operations.is_empty() || batch.operations.len() > MAX_OPERATIONS {
+        return Err(CustomError::BadRequest);
+    }
+
+    let response = match batch.mode {
+        Mode::Atomic => run_atomic(&tenant, &pii, &pool, batch.operations).await?,
+        Mode::Independent => run_independent(&tenant, &pii, &pool, batch.operations).await?,
+    };
+
+    let ids: Vec<i32> = response.results.iter().filter_map(|result| result.employee_id).collect();
+    for &id in &ids {
+        cache.invalidate(tenant.id, id).await;
+    }
+
+    Ok((StatusCode::OK, Extension(Subjects(ids)), Json(response)))
+}
+
+// Stops at the first failure and rolls everything back; operations that did
+// not get to run are reported as failed dependencies.
+async fn run_atomic(tenant: &Tenant, pii: &Pii, pool: &PgPool, operations: Vec<Operation>) -> Result<BatchResponse, CustomError> {
+    let mut tx = tenant.begin(pool).await?;
+    let mut results = Vec::with_capacity(operations.len());
+    let mut failed = false;
+
+    for operation in operations {
+        if failed {
+            results.push(OperationResult {
+                status: StatusCode::FAILED_DEPENDENCY.as_u16(),
+                body: json!({"Error": "Not Executed"}),
+                employee_id: None,
+            });
+            continue;
+        }
+
+        let result = apply(&mut tx, tenant, pii, operation).await;
+        failed = result.status >= 400;
+        results.push(result);
+    }
+
+    if failed {
+        tx.rollback().await.map_err(|_| CustomError::InternalServerError)?;
+        for result in results.iter_mut().filter(|result| result.status < 400) {
+            result.status = StatusCode::FAILED_DEPENDENCY.as_u16();
+            result.body = json!({"Error": "Rolled Back"});
+        }
+    } else {
+        tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    }
+
+    Ok(BatchResponse { committed: !failed, results })
+}
+
+async fn run_independent(tenant: &Tenant, pii: &Pii, pool: &PgPool, operations: Vec<Operation>) -> Result<BatchResponse, CustomError> {
+    let mut results = Vec::with_capacity(operations.len());
+
+    for operation in operations {
+        let mut tx = tenant.begin(pool).await?;
+        let result = apply(&mut tx, tenant, pii, operation).await;
+        if result.status < 400 {
+            tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+        }
+        results.push(result);
+    }
+
+    let committed = results.iter().any(|result| result.status < 400);
+    Ok(BatchResponse { committed, results })
+}
+
+async fn apply(conn: &mut sqlx::PgConnection, tenant: &Tenant, pii: &Pii, operation: Operation) -> OperationResult {
+    let outcome = match operation {
+        Operation::Create { data } => views::insert_profile(conn, tenant, pii, &data)
+            .await
+            .map(|_| (StatusCode::CREATED, json!(data), Some(data.id))),
+        Operation::Update { id, data } => views::modify_profile(conn, tenant, pii, id, &data)
+            .await
+            .map(|id| (StatusCode::OK, json!(data), Some(id))),
+        Operation::Delete { id } => views::remove_profile(conn, tenant, id)
+            .await
+            .map(|id| (StatusCode::OK, json!({"msg": "Profile Deleted"}), Some(id))),
+    };
+
+    match outcome {
+        Ok((status, body, employee_id)) => OperationResult { status: status.as_u16(), body, employee_id },
+        Err(error) => {
+            let (status, _) = error.status_and_message();
+            OperationResult { status: status.as_u16(), body: error.body(), employee_id: None }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use axum::{Extension, Json};
+use serde::Serialize;
+
+use crate::{api_keys::GlobalAdmin, errors::CustomError, models::Profile};
+
+// Backing storage for cached profiles. Values are opaque bytes so an external
+// store (e.g. Redis or memcached) can be plugged in instead of the in-process
+// LRU.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, key: &str) -> Option<Vec<u8>>;
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration);
+    async fn remove(&self, key: &str);
+    async fn clear(&self);
+}
+
+struct Entry {
+    value: Vec<u8>,
+    expires: Instant,
+    used: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<String, Entry>,
+    // Last-use tick to key, oldest first.
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, key: &str) {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.order.remove(&entry.used);
+            entry.used = self.tick;
+            self.order.insert(self.tick, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.used);
+        }
+    }
+}
+
+// An in-process store that evicts the least recently used entry once full.
+pub struct MemoryStore {
+    capacity: usize,
+    lru: Mutex<Lru>,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, lru: Mutex::new(Lru::default()) }
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryStore {
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut lru = self.lru.lock().unwrap();
+        match lru.entries.get(key) {
+            Some(entry) if entry.expires > Instant::now() => {
+                let value = entry.value.clone();
+                lru.touch(key);
+                Some(value)
+            }
+            Some(_) => {
+                lru.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) {
+        let mut lru = self.lru.lock().unwrap();
+        lru.remove(key);
+        while lru.entries.len() >= self.capacity {
+            let Some((_, oldest)) = lru.order.pop_first() else { break };
+            lru.entries.remove(&oldest);
+        }
+
+        lru.tick += 1;
+        let used = lru.tick;
+        lru.order.insert(used, key.to_string());
+        lru.entries.insert(key.to_string(), Entry { value, expires: Instant::now() + ttl, used });
+    }
+
+    async fn remove(&self, key: &str) {
+        self.lru.lock().unwrap().remove(key);
+    }
+
+    async fn clear(&self) {
+        *self.lru.lock().unwrap() = Lru::default();
+    }
+}
+
+// Read-through cache of employee rows as stored, so contact fields stay
+// encrypted in the cache and are revealed per caller like a database read.
+pub struct ProfileCache {
+    store: Option<Arc<dyn CacheStore>>,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ProfileCache {
+    // A cache without a store never holds anything.
+    pub fn new(store: Option<Arc<dyn CacheStore>>, ttl: Duration) -> Self {
+        Self { store, ttl, hits: AtomicU64::new(0), misses: AtomicU64::new(0) }
+    }
+
+    fn key(tenant_id: i32, id: i32) -> String {
+        format!("profile:{tenant_id}:{id}")
+    }
+
+    pub async fn get(&self, tenant_id: i32, id: i32) -> Option<Profile> {
+        let store = self.store.as_ref()?;
+        let cached = store
+            .get(&Self::key(tenant_id, id))
+            .await
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+
+        let counter = if cached.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(employee_id = id, hit = cached.is_some(), "profile cache lookup");
+        cached
+    }
+
+    pub async fn put(&self, tenant_id: i32, profile: &Profile) {
+        let Some(store) = &self.store else { return };
+        if let Ok(bytes) = serde_json::to_vec(profile) {
+            store.set(&Self::key(tenant_id, profile.id), bytes, self.ttl).await;
+        }
+    }
+
+    pub async fn invalidate(&self, tenant_id: i32, id: i32) {
+        if let Some(store) = &self.store {
+            store.remove(&Self::key(tenant_id, id)).await;
+        }
+    }
+
+    pub async fn clear(&self) {
+        if let Some(store) = &self.store {
+            store.clear().await;
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub enabled: bool,
+    pub hits: u64,
+    pub misses: u64,
+    pub ttl_secs: u64,
+}
+
+pub async fn cache_stats(_: GlobalAdmin, Extension(cache): Extension<Arc<ProfileCache>>) -> Result<Json<CacheStats>, CustomError> {
+    Ok(Json(CacheStats {
+        enabled: cache.store.is_some(),
+        hits: cache.hits.load(Ordering::Relaxed),
+        misses: cache.misses.load(Ordering::Relaxed),
+        ttl_secs: cache.ttl.as_secs(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn evicts_the_least_recently_used_entry() {
+        let store = MemoryStore::new(2);
+        store.set("a", b"1".to_vec(), TTL).await;
+        store.set("b", b"2".to_vec(), TTL).await;
+        // Reading `a` makes `b` the oldest.
+        assert_eq!(store.get("a").await, Some(b"1".to_vec()));
+
+        store.set("c", b"3".to_vec(), TTL).await;
+
+        assert_eq!(store.get("a").await, Some(b"1".to_vec()));
+        assert_eq!(store.get("b").await, None);
+        assert_eq!(store.get("c").await, Some(b"3".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn replacing_an_entry_does_not_evict_another() {
+        let store = MemoryStore::new(2);
+        store.set("a", b"1".to_vec(), TTL).await;
+        store.set("b", b"2".to_vec(), TTL).await;
+        store.set("a", b"3".to_vec(), TTL).await;
+
+        assert_eq!(store.get("a").await, Some(b"3".to_vec()));
+        assert_eq!(store.get("b").await, Some(b"2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn drops_expired_entries() {
+        let store = MemoryStore::new(2);
+        store.set("a", b"1".to_vec(), Duration::ZERO).await;
+
+        assert_eq!(store.get("a").await, None);
+        let lru = store.lru.lock().unwrap();
+        assert!(lru.entries.is_empty() && lru.order.is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use std::{fs, io::Write, path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+use crate::{
+    config::Config,
+    models::{NewProfile, Profile, ProfileFilter, Status},
+    negotiate::Representation,
+    pii::{Pii, PiiAccess},
+    seed::{self, Preset},
+    tenants::Tenant,
+    views,
+};
+
+#[derive(Parser)]
+#[command(about = "Employee profile API server and admin tools")]
+pub struct Cli {
+    /// Tenant slug to act on [default: DEFAULT_TENANT]
+    #[arg(long, global = true)]
+    pub tenant: Option<String>,
+
+    /// How command results are printed
+    #[arg(long, global = true, value_enum, default_value_t = Output::Table)]
+    pub output: Output,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default)
+    Serve,
+    /// Inspect or change employee profiles
+    #[command(subcommand)]
+    Employee(EmployeeCommand),
+    /// Create profiles from a JSON array or CSV file in one transaction
+    Import { file: PathBuf },
+    /// Write every profile of the tenant, contact fields decrypted
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Insert realistic fake profiles, the same ones for the same seed
+    Seed {
+        /// Dataset size to generate
+        #[arg(long, value_enum, default_value_t = Preset::Dev)]
+        preset: Preset,
+        /// Number of profiles, overriding the preset
+        #[arg(long)]
+        count: Option<usize>,
+        /// The same seed generates the same profiles
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum EmployeeCommand {
+    /// List profiles, optionally filtered by exact email, phone number or status
+    List {
+        #[arg(long)]
+        email: Option<String>,
+        #[arg(long)]
+        contact: Option<String>,
+        #[arg(long, value_enum)]
+        status: Option<Status>,
+        /// Show contact fields unmasked
+        #[arg(long)]
+        reveal: bool,
+    },
+    /// Show one profile
+    Get {
+        id: i32,
+        /// Show contact fields unmasked
+        #[arg(long)]
+        reveal: bool,
+    },
+    /// Create a profile
+    Create {
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Replace a profile
+    Update {
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Delete a profile
+    Delete { id: i32 },
+}
+
+#[derive(clap::Args)]
+pub struct ProfileArgs {
+    #[arg(long)]
+    id: i32,
+    #[arg(long)]
+    eid: String,
+    #[arg(long)]
+    name: String,
+    #[arg(long)]
+    email: String,
+    #[arg(long)]
+    contact: String,
+    /// A custom field value as name=value; repeat for more fields
+    #[arg(long = "field", value_parser = parse_field)]
+    fields: Vec<(String, String)>,
+}
+
+fn parse_field(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .ok_or_else(|| "expected name=value".to_string())
+}
+
+impl From<ProfileArgs> for NewProfile {
+    fn from(args: ProfileArgs) -> Self {
+        // Without --field an update keeps the stored values.
+        let custom = (!args.fields.is_empty()).then(|| args.fields.into_iter().map(|(name, value)| (name, value.into())).collect());
+        NewProfile { id: args.id, eid: args.eid, ename: args.name, eemail: args.email, econtact: args.contact, custom }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+// Runs one admin command against the database directly, through the same
+// queries the HTTP handlers use. A running server may keep serving its cached
+// copy of a changed profile until the cache entry expires.
+pub async fn run(command: Command, tenant: Option<String>, output: Output, config: Arc<Config>) -> anyhow::Result<()> {
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&config.database_url)
+        .await
+        .context("Could not connect to the database_url")?;
+    let pii = Pii::from_config(&config).context("Invalid PII encryption settings")?;
+
+    let slug = tenant
+        .or_else(|| config.default_tenant.clone())
+        .context("No tenant given and DEFAULT_TENANT is not set")?;
+    let tenant = Tenant::from_slug(&pool, &config, &slug)
+        .await
+        .with_context(|| format!("Unknown tenant {slug:?}"))?;
+
+    match command {
+        Command::Serve => unreachable!("handled by main"),
+        Command::Employee(command) => employee(command, &tenant, &pii, &pool, output).await,
+        Command::Import { file } => import(&file, &tenant, &pii, &pool, output).await,
+        Command::Export { format, file } => export(format, file, &tenant, &pii, &pool).await,
+        Command::Seed { preset, count, seed } => {
+            let count = count.unwrap_or(preset.count());
+            let ids = seed::seed(&pool, &tenant, &pii, seed, count).await?;
+            if ids.is_empty() {
+                return print_message("Seeded no employees", &serde_json::json!({"seeded": 0, "first_id": null, "last_id": null}), output);
+            }
+            let message = format!("Seeded {count} employees (ids {} to {})", ids.start(), ids.end());
+            print_message(&message, &serde_json::json!({"seeded": count, "first_id": ids.start(), "last_id": ids.end()}), output)
+        }
+    }
+}
+
+async fn employee(command: EmployeeCommand, tenant: &Tenant, pii: &Pii, pool: &PgPool, output: Output) -> anyhow::Result<()> {
+    let mut tx = tenant.begin(pool).await?;
+
+    match command {
+        EmployeeCommand::List { email, contact, status, reveal } => {
+            let filter = ProfileFilter { eemail: email, econtact: contact, status, ..Default::default() };
+            let mut profiles = views::find_profiles(&mut tx, tenant, pii, &filter).await?;
+            for profile in &mut profiles {
+                pii.reveal(profile, &PiiAccess(reveal))?;
+            }
+            print_profiles(&profiles, output)
+        }
+        EmployeeCommand::Get { id, reveal } => {
+            let mut profile = views::find_profile(&mut tx, tenant, id).await?;
+            pii.reveal(&mut profile, &PiiAccess(reveal))?;
+            match output {
+                Output::Table => print_profiles(std::slice::from_ref(&profile), output),
+                Output::Json => print_json(&profile),
+            }
+        }
+        EmployeeCommand::Create { profile } => {
+            let data = NewProfile::from(profile);
+            views::insert_profile(&mut tx, tenant, pii, &data).await?;
+            tx.commit().await?;
+            print_message(&format!("Created employee {}", data.id), &data, output)
+        }
+        EmployeeCommand::Update { profile } => {
+            let data = NewProfile::from(profile);
+            views::modify_profile(&mut tx, tenant, pii, data.id, &data).await?;
+            tx.commit().await?;
+            print_message(&format!("Updated employee {}", data.id), &data, output)
+        }
+        EmployeeCommand::Delete { id } => {
+            views::remove_profile(&mut tx, tenant, id).await?;
+            tx.commit().await?;
+            print_message(&format!("Deleted employee {id}"), &serde_json::json!({"msg": "Profile Deleted", "id": id}), output)
+        }
+    }
+}
+
+async fn import(file: &PathBuf, tenant: &Tenant, pii: &Pii, pool: &PgPool, output: Output) -> anyhow::Result<()> {
+    let contents = fs::read(file).with_context(|| format!("Could not read {}", file.display()))?;
+    let profiles: Vec<NewProfile> = match file.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => csv::Reader::from_reader(contents.as_slice())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .context("Invalid CSV")?,
+        _ => serde_json::from_slice(&contents).context("Expected a JSON array of profiles")?,
+    };
+
+    let mut tx = tenant.begin(pool).await?;
+    for (line, data) in profiles.iter().enumerate() {
+        views::insert_profile(&mut tx, tenant, pii, data)
+            .await
+            .with_context(|| format!("Could not import record {} (id {})", line + 1, data.id))?;
+    }
+    tx.commit().await?;
+
+    let count = profiles.len();
+    print_message(&format!("Imported {count} employees"), &serde_json::json!({"imported": count}), output)
+}
+
+async fn export(format: ExportFormat, file: Option<PathBuf>, tenant: &Tenant, pii: &Pii, pool: &PgPool) -> anyhow::Result<()> {
+    let mut tx = tenant.begin(pool).await?;
+    let filter = ProfileFilter::default();
+    let mut profiles = views::find_profiles(&mut tx, tenant, pii, &filter).await?;
+    for profile in &mut profiles {
+        pii.reveal(profile, &PiiAccess(true))?;
+    }
+
+    let body = match format {
+        ExportFormat::Json => serde_json::to_vec_pretty(&profiles)?,
+        ExportFormat::Csv => profiles.to_csv()?,
+    };
+
+    match file {
+        Some(path) => fs::write(&path, body).with_context(|| format!("Could not write {}", path.display())),
+        None => Ok(std::io::stdout().write_all(&body)?),
+    }
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<()> {
+    writeln!(std::io::stdout(), "{}", serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+fn print_message<T: Serialize>(message: &str, value: &T, output: Output) -> anyhow::Result<()> {
+    match output {
+        Output::Table => {
+            writeln!(std::io::stdout(), "{message}")?;
+            Ok(())
+        }
+        Output::Json => print_json(value),
+    }
+}
+
+fn print_profiles(profiles: &[Profile], output: Output) -> anyhow::Result<()> {
+    if let Output::Json = output {
+        return print_json(profiles);
+    }
+
+    let header = ["ID", "EID", "NAME", "EMAIL", "CONTACT", "STATUS"].map(String::from);
+    let rows: Vec<[String; 6]> = profiles
+        .iter()
+        .map(|p| [p.id.to_string(), p.eid.clone(), p.ename.clone(), p.eemail.clone(), p.econtact.clone(), p.status.as_str().to_string()])
+        .collect();
+
+    let mut widths = header.clone().map(|cell| cell.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{cell:<width$}")).collect();
+        writeln!(std::io::stdout(), "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
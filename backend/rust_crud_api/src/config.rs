@@ -0,0 +1,255 @@
+use std::{collections::HashMap, fs};
+
+use anyhow::Context;
+
+use crate::rate_limit::{Limit, RouteLimit};
+
+// Settings are read from the process environment first and the `.env` file
+// second, so deployments can override anything the file sets.
+pub struct Config {
+    pub database_url: String,
+    pub database_replica_urls: Vec<String>,
+    pub replica_sticky_secs: u64,
+    pub log_filter: String,
+    pub log_json: bool,
+    pub otlp_endpoint: Option<String>,
+    pub otel_service_name: String,
+    pub admin_api_key: Option<String>,
+    pub jwt_secret: Option<String>,
+    pub default_tenant: Option<String>,
+    pub tenant_base_domain: Option<String>,
+    pub tenant_rls: bool,
+    pub pii_keys: String,
+    pub pii_active_key: String,
+    pub pii_index_key: String,
+    pub idempotency_ttl_secs: i64,
+    pub idempotency_lease_secs: i64,
+    pub job_poll_secs: u64,
+    pub job_run_retention_days: i64,
+    pub notifier: Option<String>,
+    pub notifier_from: String,
+    pub sms_gateway_domain: Option<String>,
+    pub verification_code_ttl_secs: i64,
+    pub verification_max_attempts: i32,
+    pub verification_resend_secs: i64,
+    pub verification_daily_attempts: i64,
+    pub profile_cache_capacity: usize,
+    pub profile_cache_ttl_secs: u64,
+    pub rate_limit_default: Limit,
+    pub rate_limit_routes: Vec<RouteLimit>,
+    pub cors_allowed_origins: Vec<String>,
+    pub cors_allowed_methods: Vec<String>,
+    pub cors_allow_credentials: bool,
+    pub cors_max_age_secs: u64,
+    pub compression: Vec<String>,
+    pub hsts_max_age_secs: u64,
+    pub content_security_policy: Option<String>,
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    pub tls_client_ca_path: Option<String>,
+    pub tls_client_auth_optional: bool,
+    pub tls_reload_secs: u64,
+    pub grpc_port: u16,
+}
+
+impl Config {
+    pub fn load() -> anyhow::Result<Self> {
+        let file = fs::read_to_string(".env").unwrap_or_default();
+        let vars = Vars::new(&file);
+
+        let database_url = vars.get("DATABASE_URL").context("DATABASE_URL is not set")?;
+
+        let rate_limit_default = match vars.get("RATE_LIMIT_DEFAULT") {
+            Some(value) => value.parse().context("Invalid RATE_LIMIT_DEFAULT")?,
+            None => Limit { capacity: 100, period_secs: 60 },
+        };
+
+        let rate_limit_routes = vars
+            .get("RATE_LIMIT_ROUTES")
+            .map(|value| {
+                value
+                    .split(';')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(str::parse)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()
+            .context("Invalid RATE_LIMIT_ROUTES")?
+            .unwrap_or_default();
+
+        let replica_sticky_secs = match vars.get("REPLICA_STICKY_SECS") {
+            Some(value) => value.parse().context("Invalid REPLICA_STICKY_SECS")?,
+            None => 5,
+        };
+
+        let idempotency_ttl_secs = match vars.get("IDEMPOTENCY_TTL_SECS") {
+            Some(value) => value.parse().context("Invalid IDEMPOTENCY_TTL_SECS")?,
+            None => 24 * 60 * 60,
+        };
+
+        let idempotency_lease_secs = match vars.get("IDEMPOTENCY_LEASE_SECS") {
+            Some(value) => value.parse().context("Invalid IDEMPOTENCY_LEASE_SECS")?,
+            None => 60,
+        };
+
+        let job_poll_secs = match vars.get("JOB_POLL_SECS") {
+            Some(value) => value.parse().context("Invalid JOB_POLL_SECS")?,
+            None => 5,
+        };
+
+        let job_run_retention_days = match vars.get("JOB_RUN_RETENTION_DAYS") {
+            Some(value) => value.parse().context("Invalid JOB_RUN_RETENTION_DAYS")?,
+            None => 30,
+        };
+
+        let verification_code_ttl_secs = match vars.get("VERIFICATION_CODE_TTL_SECS") {
+            Some(value) => value.parse().context("Invalid VERIFICATION_CODE_TTL_SECS")?,
+            None => 10 * 60,
+        };
+
+        let verification_max_attempts = match vars.get("VERIFICATION_MAX_ATTEMPTS") {
+            Some(value) => value.parse().context("Invalid VERIFICATION_MAX_ATTEMPTS")?,
+            None => 5,
+        };
+
+        let verification_resend_secs = match vars.get("VERIFICATION_RESEND_SECS") {
+            Some(value) => value.parse().context("Invalid VERIFICATION_RESEND_SECS")?,
+            None => 60,
+        };
+
+        let verification_daily_attempts = match vars.get("VERIFICATION_DAILY_ATTEMPTS") {
+            Some(value) => value.parse().context("Invalid VERIFICATION_DAILY_ATTEMPTS")?,
+            None => 10,
+        };
+
+        let grpc_port = match vars.get("GRPC_PORT") {
+            Some(value) => value.parse().context("Invalid GRPC_PORT")?,
+            None => 50051,
+        };
+
+        let profile_cache_capacity = match vars.get("PROFILE_CACHE_CAPACITY") {
+            Some(value) => value.parse().context("Invalid PROFILE_CACHE_CAPACITY")?,
+            None => 10_000,
+        };
+
+        let profile_cache_ttl_secs = match vars.get("PROFILE_CACHE_TTL_SECS") {
+            Some(value) => value.parse().context("Invalid PROFILE_CACHE_TTL_SECS")?,
+            None => 60,
+        };
+
+        let cors_max_age_secs = match vars.get("CORS_MAX_AGE_SECS") {
+            Some(value) => value.parse().context("Invalid CORS_MAX_AGE_SECS")?,
+            None => 600,
+        };
+
+        let hsts_max_age_secs = match vars.get("HSTS_MAX_AGE_SECS") {
+            Some(value) => value.parse().context("Invalid HSTS_MAX_AGE_SECS")?,
+            None => 0,
+        };
+
+        let tls_reload_secs = match vars.get("TLS_RELOAD_SECS") {
+            Some(value) => value.parse().context("Invalid TLS_RELOAD_SECS")?,
+            None => 30,
+        };
+
+        Ok(Self {
+            database_url,
+            database_replica_urls: vars.list("DATABASE_REPLICA_URLS").unwrap_or_default(),
+            replica_sticky_secs,
+            log_filter: vars
+                .get("RUST_LOG")
+                .filter(|filter| !filter.is_empty())
+                .unwrap_or_else(|| "rust_crud_api=debug,tower_http=debug".to_string()),
+            log_json: vars.get("LOG_FORMAT").is_some_and(|format| format.eq_ignore_ascii_case("json")),
+            otlp_endpoint: vars.get("OTEL_EXPORTER_OTLP_ENDPOINT").filter(|endpoint| !endpoint.is_empty()),
+            otel_service_name: vars
+                .get("OTEL_SERVICE_NAME")
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| "rust_crud_api".to_string()),
+            admin_api_key: vars.get("ADMIN_API_KEY").filter(|key| !key.is_empty()),
+            jwt_secret: vars.get("JWT_SECRET").filter(|secret| !secret.is_empty()),
+            default_tenant: vars.get("DEFAULT_TENANT").filter(|slug| !slug.is_empty()),
+            tenant_base_domain: vars.get("TENANT_BASE_DOMAIN").filter(|domain| !domain.is_empty()),
+            tenant_rls: vars.flag("TENANT_RLS"),
+            pii_keys: vars.get("PII_KEYS").context("PII_KEYS is not set")?,
+            pii_active_key: vars.get("PII_ACTIVE_KEY").context("PII_ACTIVE_KEY is not set")?,
+            pii_index_key: vars.get("PII_INDEX_KEY").context("PII_INDEX_KEY is not set")?,
+            idempotency_ttl_secs,
+            idempotency_lease_secs,
+            job_poll_secs,
+            job_run_retention_days,
+            notifier: vars.get("NOTIFIER").filter(|notifier| !notifier.is_empty()),
+            notifier_from: vars
+                .get("NOTIFIER_FROM")
+                .filter(|from| !from.is_empty())
+                .unwrap_or_else(|| "no-reply@localhost".to_string()),
+            sms_gateway_domain: vars.get("SMS_GATEWAY_DOMAIN").filter(|domain| !domain.is_empty()),
+            verification_code_ttl_secs,
+            verification_max_attempts,
+            verification_resend_secs,
+            verification_daily_attempts,
+            profile_cache_capacity,
+            profile_cache_ttl_secs,
+            cors_allowed_origins: vars.list("CORS_ALLOWED_ORIGINS").unwrap_or_default(),
+            cors_allowed_methods: vars
+                .list("CORS_ALLOWED_METHODS")
+                .unwrap_or_else(|| ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec()),
+            cors_allow_credentials: vars.flag("CORS_ALLOW_CREDENTIALS"),
+            cors_max_age_secs,
+            compression: vars
+                .list("COMPRESSION")
+                .unwrap_or_else(|| ["gzip", "br", "zstd"].map(String::from).to_vec()),
+            hsts_max_age_secs,
+            content_security_policy: match vars.get("CONTENT_SECURITY_POLICY") {
+                Some(csp) if csp.is_empty() => None,
+                Some(csp) => Some(csp),
+                None => Some("default-src 'none'; frame-ancestors 'none'".to_string()),
+            },
+            tls_cert_path: vars.get("TLS_CERT_PATH").filter(|path| !path.is_empty()),
+            tls_key_path: vars.get("TLS_KEY_PATH").filter(|path| !path.is_empty()),
+            tls_client_ca_path: vars.get("TLS_CLIENT_CA_PATH").filter(|path| !path.is_empty()),
+            tls_client_auth_optional: vars.flag("TLS_CLIENT_AUTH_OPTIONAL"),
+            tls_reload_secs,
+            grpc_port,
+            rate_limit_default,
+            rate_limit_routes,
+        })
+    }
+}
+
+struct Vars {
+    file: HashMap<String, String>,
+}
+
+impl Vars {
+    fn new(contents: &str) -> Self {
+        let file = contents
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+
+        Self { file }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        std::env::var(key).ok().or_else(|| self.file.get(key).cloned())
+    }
+
+    // A comma-separated list; `None` when the variable is unset.
+    fn list(&self, key: &str) -> Option<Vec<String>> {
+        self.get(key).map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect()
+        })
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.get(key).is_some_and(|value| matches!(value.as_str(), "1" | "true" | "yes"))
+    }
+}
@@ -0,0 +1,303 @@
+use std::sync::{Arc, OnceLock};
+
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Number, Value};
+use sqlx::{PgConnection, PgPool};
+use tracing::Instrument;
+
+use crate::{api_keys::Admin, cache::ProfileCache, errors::CustomError, logging::query_span, tenants::Tenant};
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum FieldKind {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Date,
+    Enum,
+}
+
+// An extra profile field defined by a tenant's admins.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct CustomField {
+    pub id: i32,
+    pub name: String,
+    pub kind: FieldKind,
+    pub required: bool,
+    pub pattern: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub options: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(flatten)]
+    #[serde(skip)]
+    pub(crate) regex: CompiledPattern,
+}
+
+// A field's pattern, compiled on first use so validating many values does not
+// compile it again for each. It is not a column; rows leave it empty.
+#[derive(Default)]
+pub(crate) struct CompiledPattern(OnceLock<Option<Regex>>);
+
+impl<'r, R: sqlx::Row> sqlx::FromRow<'r, R> for CompiledPattern {
+    fn from_row(_: &'r R) -> Result<Self, sqlx::Error> {
+        Ok(Self::default())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NewCustomField {
+    pub name: String,
+    pub kind: FieldKind,
+    #[serde(default)]
+    pub required: bool,
+    pub pattern: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub options: Option<Vec<String>>,
+}
+
+const FIELD_COLUMNS: &str = "id, name, kind, required, pattern, min, max, options, created_at";
+
+impl CustomField {
+    // The value in its stored form, or None when it does not fit the field.
+    // Strings are accepted for every kind so CSV, XML and query parameters can
+    // carry typed values.
+    pub fn coerce(&self, value: &Value) -> Option<Value> {
+        let text = value.as_str().map(str::trim);
+        let value = match self.kind {
+            FieldKind::String | FieldKind::Enum => Value::String(value.as_str()?.to_string()),
+            FieldKind::Integer => match value {
+                Value::Number(number) => Value::from(number.as_i64()?),
+                _ => Value::from(text?.parse::<i64>().ok()?),
+            },
+            FieldKind::Number => match value {
+                Value::Number(number) => Value::Number(number.clone()),
+                _ => Value::Number(Number::from_f64(text?.parse().ok()?)?),
+            },
+            FieldKind::Boolean => match value {
+                Value::Bool(flag) => Value::Bool(*flag),
+                _ => Value::Bool(text?.parse().ok()?),
+            },
+            FieldKind::Date => Value::String(NaiveDate::parse_from_str(text?, "%Y-%m-%d").ok()?.to_string()),
+        };
+
+        self.check(&value).then_some(value)
+    }
+
+    fn check(&self, value: &Value) -> bool {
+        let measure = match (self.kind, value) {
+            (FieldKind::String, Value::String(text)) => Some(text.chars().count() as f64),
+            (FieldKind::Integer | FieldKind::Number, Value::Number(number)) => number.as_f64(),
+            _ => None,
+        };
+        if let Some(measure) = measure {
+            if self.min.is_some_and(|min| measure < min) || self.max.is_some_and(|max| measure > max) {
+                return false;
+            }
+        }
+
+        match (self.kind, value) {
+            (FieldKind::String, Value::String(text)) => match &self.pattern {
+                Some(pattern) => self.regex.0.get_or_init(|| full_match(pattern)).as_ref().is_some_and(|regex| regex.is_match(text)),
+                None => true,
+            },
+            (FieldKind::Enum, Value::String(text)) => self.options.iter().flatten().any(|option| option == text),
+            _ => true,
+        }
+    }
+}
+
+fn full_match(pattern: &str) -> Option<Regex> {
+    Regex::new(&format!("^(?:{pattern})$")).ok()
+}
+
+pub async fn fields(conn: &mut PgConnection, tenant: &Tenant) -> Result<Vec<CustomField>, CustomError> {
+    let sql = format!("SELECT {FIELD_COLUMNS} FROM custom_field WHERE tenant_id=$1 ORDER BY id");
+    sqlx::query_as(&sql)
+    .bind(tenant.id)
+    .fetch_all(conn)
+    .instrument(query_span(&sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })
+}
+
+// Checks submitted values against the tenant's fields and returns them in
+// their stored form. Unknown fields are rejected; a null value counts as
+// missing.
+pub async fn validate(conn: &mut PgConnection, tenant: &Tenant, values: &Map<String, Value>) -> Result<Map<String, Value>, CustomError> {
+    let fields = fields(conn, tenant).await?;
+
+    if let Some(unknown) = values.keys().find(|name| !fields.iter().any(|field| &field.name == *name)) {
+        return Err(CustomError::InvalidCustomField(unknown.clone()));
+    }
+
+    let mut stored = Map::new();
+    for field in &fields {
+        match values.get(&field.name) {
+            None | Some(Value::Null) if field.required => return Err(CustomError::InvalidCustomField(field.name.clone())),
+            None | Some(Value::Null) => {}
+            Some(value) => {
+                let value = field.coerce(value).ok_or_else(|| CustomError::InvalidCustomField(field.name.clone()))?;
+                stored.insert(field.name.clone(), value);
+            }
+        }
+    }
+
+    Ok(stored)
+}
+
+pub async fn list_fields(_: Admin, tenant: Tenant, Extension(pool): Extension<PgPool>) -> Result<Json<Vec<CustomField>>, CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+    Ok(Json(fields(&mut tx, &tenant).await?))
+}
+
+// Required fields apply to profiles created or updated from now on; existing
+// profiles are not checked.
+pub async fn create_field(_: Admin, tenant: Tenant, Extension(pool): Extension<PgPool>, Json(data): Json<NewCustomField>) -> Result<(StatusCode, Json<CustomField>), CustomError> {
+    let name_valid = Regex::new("^[a-z][a-z0-9_]{0,62}$").is_ok_and(|regex| regex.is_match(&data.name));
+    let pattern_valid = match (&data.pattern, data.kind) {
+        (None, _) => true,
+        (Some(pattern), FieldKind::String) => full_match(pattern).is_some(),
+        (Some(_), _) => false,
+    };
+    let options_valid = match (&data.options, data.kind) {
+        (Some(options), FieldKind::Enum) => !options.is_empty(),
+        (None, FieldKind::Enum) => false,
+        (options, _) => options.is_none(),
+    };
+    let bounds_valid = match (data.min, data.max) {
+        (Some(min), Some(max)) => min <= max,
+        _ => true,
+    };
+    if !(name_valid && pattern_valid && options_valid && bounds_valid) {
+        return Err(CustomError::UnprocessableEntity);
+    }
+
+    let sql = format!("INSERT INTO custom_field (tenant_id, name, kind, required, pattern, min, max, options) values ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (tenant_id, name) DO NOTHING RETURNING {FIELD_COLUMNS}");
+    let field = sqlx::query_as(&sql)
+    .bind(tenant.id)
+    .bind(&data.name)
+    .bind(data.kind)
+    .bind(data.required)
+    .bind(&data.pattern)
+    .bind(data.min)
+    .bind(data.max)
+    .bind(&data.options)
+    .fetch_optional(&pool)
+    .instrument(query_span(&sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?
+    .ok_or(CustomError::Conflict)?;
+
+    Ok((StatusCode::CREATED, Json(field)))
+}
+
+// Removes the field and its values from every profile of the tenant.
+pub async fn delete_field(_: Admin, Path(name): Path<String>, tenant: Tenant, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>) -> Result<(StatusCode, Json<Value>), CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+
+    let sql = "DELETE FROM custom_field WHERE tenant_id=$1 AND name=$2";
+    let result = sqlx::query(sql)
+    .bind(tenant.id)
+    .bind(&name)
+    .execute(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err(CustomError::TaskNotFound);
+    }
+
+    let sql = "UPDATE employee SET custom = custom - $2 WHERE tenant_id=$1 AND custom ? $2";
+    sqlx::query(sql)
+    .bind(tenant.id)
+    .bind(&name)
+    .execute(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    cache.clear().await;
+
+    Ok((StatusCode::OK, Json(json!({"msg": "Custom Field Deleted"}))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(kind: FieldKind) -> CustomField {
+        CustomField {
+            id: 1,
+            name: "field".to_string(),
+            kind,
+            required: false,
+            pattern: None,
+            min: None,
+            max: None,
+            options: None,
+            created_at: Utc::now(),
+            regex: CompiledPattern::default(),
+        }
+    }
+
+    #[test]
+    fn coerces_strings_to_the_field_kind() {
+        assert_eq!(field(FieldKind::Integer).coerce(&json!(" 42 ")), Some(json!(42)));
+        assert_eq!(field(FieldKind::Number).coerce(&json!("2.5")), Some(json!(2.5)));
+        assert_eq!(field(FieldKind::Boolean).coerce(&json!("true")), Some(json!(true)));
+        assert_eq!(field(FieldKind::Date).coerce(&json!("2024-02-29")), Some(json!("2024-02-29")));
+    }
+
+    #[test]
+    fn rejects_values_of_another_kind() {
+        assert_eq!(field(FieldKind::Integer).coerce(&json!(1.5)), None);
+        assert_eq!(field(FieldKind::Integer).coerce(&json!("ten")), None);
+        assert_eq!(field(FieldKind::Boolean).coerce(&json!("yes")), None);
+        assert_eq!(field(FieldKind::Date).coerce(&json!("2023-02-29")), None);
+        assert_eq!(field(FieldKind::String).coerce(&json!(3)), None);
+    }
+
+    #[test]
+    fn checks_bounds_by_value_or_length() {
+        let mut level = field(FieldKind::Integer);
+        level.min = Some(1.0);
+        level.max = Some(5.0);
+        assert!(level.check(&json!(5)));
+        assert!(!level.check(&json!(6)));
+
+        let mut code = field(FieldKind::String);
+        code.max = Some(3.0);
+        assert!(code.check(&json!("äöü")));
+        assert!(!code.check(&json!("abcd")));
+    }
+
+    #[test]
+    fn matches_the_whole_string_against_the_pattern() {
+        let mut code = field(FieldKind::String);
+        code.pattern = Some("[A-Z]{2}[0-9]+".to_string());
+        assert!(code.check(&json!("AB12")));
+        assert!(!code.check(&json!("xAB12")));
+        assert!(!code.check(&json!("AB12x")));
+    }
+
+    #[test]
+    fn accepts_only_listed_options() {
+        let mut grade = field(FieldKind::Enum);
+        grade.options = Some(vec!["junior".to_string(), "senior".to_string()]);
+        assert_eq!(grade.coerce(&json!("senior")), Some(json!("senior")));
+        assert_eq!(grade.coerce(&json!("lead")), None);
+    }
+}
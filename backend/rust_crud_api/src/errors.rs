@@ -1,22 +1,84 @@
 use axum::{http::StatusCode, response::IntoResponse, Json};
 use serde_json::json;
 
+#[derive(Debug)]
 pub enum CustomError {
-    _BadRequest,
+    BadRequest,
     TaskNotFound,
+    Unauthorized,
+    Forbidden,
+    NotAcceptable,
+    Conflict,
+    UnsupportedMediaType,
+    UnprocessableEntity,
+    PayloadTooLarge,
+    // Names the custom field whose value is missing or invalid.
+    InvalidCustomField(String),
+    TooManyRequests,
     InternalServerError
 }
 
-impl IntoResponse for CustomError {
-    fn into_response(self)-> axum::response::Response {
-        let (status, error_message) = match self {
+impl CustomError {
+    pub fn status_and_message(&self) -> (StatusCode, &'static str) {
+        match self {
             Self::InternalServerError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal Server Error",
             ),
-            Self::_BadRequest => (StatusCode::BAD_REQUEST, "Bad Request"),
-            Self::TaskNotFound => (StatusCode::NOT_FOUND, "Information Not Found")
+            Self::BadRequest => (StatusCode::BAD_REQUEST, "Bad Request"),
+            Self::TaskNotFound => (StatusCode::NOT_FOUND, "Information Not Found"),
+            Self::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
+            Self::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
+            Self::NotAcceptable => (StatusCode::NOT_ACCEPTABLE, "Not Acceptable"),
+            Self::Conflict => (StatusCode::CONFLICT, "Conflict"),
+            Self::UnsupportedMediaType => (StatusCode::UNSUPPORTED_MEDIA_TYPE, "Unsupported Media Type"),
+            Self::UnprocessableEntity => (StatusCode::UNPROCESSABLE_ENTITY, "Unprocessable Entity"),
+            Self::PayloadTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "Payload Too Large"),
+            Self::InvalidCustomField(_) => (StatusCode::UNPROCESSABLE_ENTITY, "Invalid Custom Field"),
+            Self::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too Many Requests")
+        }
+    }
+
+    pub fn body(&self) -> serde_json::Value {
+        let (_, error_message) = self.status_and_message();
+        match self {
+            Self::InvalidCustomField(field) => json!({"Error": error_message, "field": field}),
+            _ => json!({"Error": error_message}),
+        }
+    }
+}
+
+impl IntoResponse for CustomError {
+    fn into_response(self)-> axum::response::Response {
+        let (status, _) = self.status_and_message();
+        (status, Json(self.body())).into_response()
+    }
+}
+
+impl std::fmt::Display for CustomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.status_and_message().1)
+    }
+}
+
+impl std::error::Error for CustomError {}
+
+// The gRPC service reports the same errors with the closest status codes.
+impl From<CustomError> for tonic::Status {
+    fn from(error: CustomError) -> Self {
+        let code = match error {
+            CustomError::BadRequest | CustomError::UnprocessableEntity | CustomError::InvalidCustomField(_) => tonic::Code::InvalidArgument,
+            CustomError::TaskNotFound => tonic::Code::NotFound,
+            CustomError::Unauthorized => tonic::Code::Unauthenticated,
+            CustomError::Forbidden => tonic::Code::PermissionDenied,
+            CustomError::Conflict => tonic::Code::FailedPrecondition,
+            CustomError::NotAcceptable | CustomError::UnsupportedMediaType => tonic::Code::Unimplemented,
+            CustomError::TooManyRequests | CustomError::PayloadTooLarge => tonic::Code::ResourceExhausted,
+            CustomError::InternalServerError => tonic::Code::Internal,
         };
-        (status, Json(json!({"Error": error_message}))).into_response()
+        match &error {
+            CustomError::InvalidCustomField(field) => tonic::Status::new(code, format!("{error}: {field}")),
+            _ => tonic::Status::new(code, error.to_string()),
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,188 @@
+use std::{
+    io::{Cursor, Write},
+    sync::Arc,
+};
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::Instrument;
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{
+    api_keys::Admin,
+    cache::ProfileCache,
+    errors::CustomError,
+    history, lifecycle,
+    logging::query_span,
+    merges,
+    models::Profile,
+    pii::{Pii, PiiAccess},
+    tenants::Tenant,
+    verification, views,
+};
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct ErasureRequest {
+    pub id: i32,
+    pub employee_id: i32,
+    pub reason: String,
+    pub requested_by: String,
+    pub requested_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct NewErasureRequest {
+    pub reason: String,
+}
+
+// Everything held about one employee, as a zip of JSON documents.
+pub async fn export_profile(Admin(_): Admin, Path(id): Path<i32>, tenant: Tenant, Extension(pii): Extension<Arc<Pii>>, Extension(pool): Extension<PgPool>) -> Result<Response, CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+    let id = views::resolve_id(&mut tx, &tenant, id).await?;
+    let sql = "SELECT * FROM employee WHERE id=$1 AND tenant_id=$2";
+    let mut profile: Profile = sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_one(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::TaskNotFound
+    })?;
+    pii.reveal(&mut profile, &PiiAccess(true))?;
+
+    let sql = "SELECT id, employee_id, reason, requested_by, requested_at, completed_at FROM erasure_request WHERE employee_id=$1 AND tenant_id=$2 ORDER BY id";
+    let erasures: Vec<ErasureRequest> = sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_all(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+    let transitions = lifecycle::transitions(&mut tx, &tenant, id).await?;
+    let mut versions = history::versions(&mut tx, &tenant, id).await?;
+    for version in &mut versions {
+        pii.reveal(&mut version.profile, &PiiAccess(true))?;
+    }
+    let verifications = verification::verifications(&mut tx, &tenant, id).await?;
+    let merges = merges::merges(&mut tx, &tenant, id).await?;
+
+    let manifest = json!({
+        "employee_id": id,
+        "generated_at": Utc::now(),
+        "files": ["profile.json", "erasure_requests.json", "status_history.json", "versions.json", "verifications.json", "merges.json"],
+    });
+
+    let archive = zip_json(&[
+        ("manifest.json", &manifest),
+        ("profile.json", &json!(profile)),
+        ("erasure_requests.json", &json!(erasures)),
+        ("status_history.json", &json!(transitions)),
+        ("versions.json", &json!(versions)),
+        ("verifications.json", &json!(verifications)),
+        ("merges.json", &json!(merges)),
+    ])
+    .map_err(|_| CustomError::InternalServerError)?;
+
+    let disposition = format!("attachment; filename=\"employee-{id}-export.zip\"");
+    Ok((
+        [(header::CONTENT_TYPE, "application/zip".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        archive,
+    ).into_response())
+}
+
+fn zip_json(files: &[(&str, &serde_json::Value)]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, value) in files {
+        zip.start_file(*name, FileOptions::default())?;
+        zip.write_all(&serde_json::to_vec_pretty(value).unwrap_or_default())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+// Anonymises the employee row in place, custom values included, so the id
+// stays valid for anything referring to it. Drops its earlier versions, the
+// codes sent to verify its contacts and the stored responses that repeat its
+// data, and records the request that caused it.
+pub async fn erase_profile(Admin(key): Admin, Path(id): Path<i32>, tenant: Tenant, Extension(pii): Extension<Arc<Pii>>, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>, Json(data): Json<NewErasureRequest>) -> Result<(StatusCode, Json<ErasureRequest>), CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+    let id = views::resolve_id(&mut tx, &tenant, id).await?;
+
+    let sql = "UPDATE employee SET eid=$1, ename=$1, eemail=$2, econtact=$3, custom='{}', eemail_bidx=NULL, econtact_bidx=NULL, eemail_domain=NULL, eemail_verified_at=NULL, econtact_verified_at=NULL, erased_at=now() WHERE id=$4 AND tenant_id=$5 AND erased_at IS NULL";
+    let result = sqlx::query(sql)
+    .bind("Erased")
+    .bind(pii.encrypt(""))
+    .bind(pii.encrypt(""))
+    .bind(id)
+    .bind(tenant.id)
+    .execute(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err(CustomError::TaskNotFound);
+    }
+
+    // Earlier versions in the history still hold the erased contact data, as
+    // do the versions of records merged into this one, which are kept under
+    // this id.
+    let sql = "DELETE FROM employee_history WHERE employee_id=$1 AND tenant_id=$2 AND valid_to IS NOT NULL";
+    sqlx::query(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .execute(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    // Verification codes are keyed by the blind index of the erased contacts.
+    let sql = "DELETE FROM contact_verification WHERE employee_id=$1 AND tenant_id=$2";
+    sqlx::query(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .execute(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    // Stored idempotent responses may repeat the contact data.
+    let sql = "DELETE FROM idempotency_key WHERE tenant_id=$2 AND $1 = ANY(employee_ids)";
+    sqlx::query(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .execute(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    let sql = "INSERT INTO erasure_request (employee_id, tenant_id, reason, requested_by, completed_at) values ($1, $2, $3, $4, now()) RETURNING id, employee_id, reason, requested_by, requested_at, completed_at";
+    let erasure = sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .bind(&data.reason)
+    .bind(&key.name)
+    .fetch_one(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    cache.invalidate(tenant.id, id).await;
+
+    Ok((StatusCode::OK, Json(erasure)))
+}
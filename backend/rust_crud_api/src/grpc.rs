@@ -0,0 +1,652 @@
+use std::{
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{self, request::Parts},
+};
+use chrono::{DateTime, TimeZone, Utc};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{
+    body::BoxBody,
+    transport::{
+        server::{TcpConnectInfo, TlsConnectInfo},
+        Certificate, Server,
+    },
+    Request, Response, Status,
+};
+use tower::{layer::layer_fn, Service};
+
+use crate::{
+    cache::ProfileCache,
+    config::Config,
+    errors::CustomError,
+    merges,
+    models::{NewProfile, Profile, ProfileFilter, Status as EmployeeStatus},
+    pii::{Pii, PiiAccess},
+    rate_limit::RateLimiter,
+    replicas::{ReadPool, ReadSource, Replicas},
+    tenants::Tenant,
+    tls::{self, ClientIdentity},
+    views, Services,
+};
+
+pub mod proto {
+    tonic::include_proto!("employee.v1");
+
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("employee_descriptor");
+}
+
+use proto::{
+    employee_event::Kind,
+    employee_service_server::{EmployeeServiceServer, EmployeeService as Rpc},
+    DeleteEmployeeRequest, DeleteEmployeeResponse, Employee, EmployeeEvent, GetEmployeeRequest, ListEmployeesRequest,
+    ListEmployeesResponse, UpdateEmployeeRequest, WatchEmployeesRequest,
+};
+
+const CHANGES_CHANNEL: &str = "employee_changes";
+
+// A committed change to an employee row, as announced by the database.
+#[derive(Clone, Deserialize)]
+struct Change {
+    op: String,
+    tenant_id: i32,
+    id: i32,
+}
+
+impl Change {
+    // Whether a Watch call on the tenant, and on one employee if `only` is
+    // set, receives the change.
+    fn watched_by(&self, tenant_id: i32, only: Option<i32>) -> bool {
+        self.tenant_id == tenant_id && only.is_none_or(|id| id == self.id)
+    }
+}
+
+// The employee API over gRPC, sharing the REST handlers' queries, validation,
+// cache and authentication.
+#[derive(Clone)]
+pub struct EmployeeService {
+    config: Arc<Config>,
+    pool: PgPool,
+    replicas: Arc<Replicas>,
+    pii: Arc<Pii>,
+    cache: Arc<ProfileCache>,
+    changes: broadcast::Sender<Change>,
+}
+
+// Serves the gRPC service, with reflection, on its own port next to the REST
+// API, with the same TLS settings and rate limits. Does nothing when
+// GRPC_PORT is 0.
+pub fn start(config: Arc<Config>, pool: PgPool, services: &Services) -> anyhow::Result<()> {
+    if config.grpc_port == 0 {
+        return Ok(());
+    }
+
+    let mut server = Server::builder();
+    if let Some(tls) = tls::grpc_config(&config)? {
+        server = server.tls_config(tls)?;
+    }
+
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
+        .build()?;
+
+    let (changes, _) = broadcast::channel(256);
+    tokio::spawn(listen(pool.clone(), changes.clone()));
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], config.grpc_port));
+    let service = EmployeeService {
+        config,
+        pool,
+        replicas: services.replicas.clone(),
+        pii: services.pii.clone(),
+        cache: services.cache.clone(),
+        changes,
+    };
+    let limiter = services.limiter.clone();
+    let limited = service.clone();
+
+    tokio::spawn(async move {
+        tracing::debug!("gRPC listening on {}", addr);
+        let served = server
+            .trace_fn(|request| tracing::info_span!("grpc", otel.kind = "server", path = %request.uri().path()))
+            .layer(layer_fn(move |inner| RateLimit { inner, service: limited.clone(), limiter: limiter.clone() }))
+            .add_service(EmployeeServiceServer::new(service))
+            .add_service(reflection)
+            .serve(addr)
+            .await;
+        if let Err(error) = served {
+            tracing::error!("gRPC server stopped: {error}");
+        }
+    });
+
+    Ok(())
+}
+
+// Relays the database's change notifications to Watch calls, reconnecting
+// when the connection drops.
+async fn listen(pool: PgPool, changes: broadcast::Sender<Change>) {
+    loop {
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                tracing::warn!("could not listen for employee changes: {error}");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        if let Err(error) = listener.listen(CHANGES_CHANNEL).await {
+            tracing::warn!("could not listen for employee changes: {error}");
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            continue;
+        }
+
+        loop {
+            match listener.recv().await {
+                Ok(notification) => match serde_json::from_str::<Change>(notification.payload()) {
+                    // No receivers just means nobody is watching.
+                    Ok(change) => _ = changes.send(change),
+                    Err(error) => tracing::warn!("malformed employee change: {error}"),
+                },
+                Err(error) => {
+                    tracing::warn!("lost the employee changes listener: {error}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// What the REST extractors would resolve for a request with these headers.
+struct Caller {
+    tenant: Tenant,
+    access: PiiAccess,
+    read_pool: PgPool,
+    read_source: ReadSource,
+}
+
+impl EmployeeService {
+    // Request parts as the REST extractors expect them: the call's metadata as
+    // headers, the shared services, and the peer address and client
+    // certificate of the connection.
+    fn parts(&self, headers: http::HeaderMap, remote_addr: Option<SocketAddr>, peer_certs: Option<Arc<Vec<Certificate>>>) -> Parts {
+        let (mut parts, ()) = http::Request::new(()).into_parts();
+        parts.headers = headers;
+        parts.extensions.insert(self.config.clone());
+        parts.extensions.insert(self.pool.clone());
+        parts.extensions.insert(self.replicas.clone());
+        if let Some(addr) = remote_addr {
+            parts.extensions.insert(ConnectInfo(addr));
+        }
+        let identity = peer_certs.as_deref().and_then(|certs| certs.first()).map(|cert| ClientIdentity::from_der(cert.get_ref()));
+        parts.extensions.insert(identity);
+        parts
+    }
+
+    async fn caller<T>(&self, request: &Request<T>) -> Result<Caller, Status> {
+        let mut parts = self.parts(request.metadata().clone().into_headers(), request.remote_addr(), request.peer_certs());
+
+        let tenant = Tenant::from_request_parts(&mut parts, &()).await?;
+        let access = PiiAccess::from_request_parts(&mut parts, &()).await?;
+        let ReadPool(read_pool, read_source) = ReadPool::from_request_parts(&mut parts, &()).await?;
+
+        Ok(Caller { tenant, access, read_pool, read_source })
+    }
+
+    // The stored employee with contact data revealed as the caller may see it.
+    fn employee(&self, caller: &Caller, mut profile: Profile) -> Result<Employee, CustomError> {
+        self.pii.reveal(&mut profile, &caller.access)?;
+        Ok(to_employee(profile))
+    }
+}
+
+// Applies the REST API's rate limits to each call, under its method's path
+// (e.g. `POST /employee.v1.EmployeeService/Get`). Calls over the limit end
+// with RESOURCE_EXHAUSTED.
+#[derive(Clone)]
+struct RateLimit<S> {
+    inner: S,
+    service: EmployeeService,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<http::Request<B>> for RateLimit<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        // The service that was polled ready handles the call; the clone waits
+        // for the next one.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let (remote_addr, peer_certs) = match request.extensions().get::<TlsConnectInfo<TcpConnectInfo>>() {
+            Some(tls) => (tls.get_ref().remote_addr(), tls.peer_certs()),
+            None => (request.extensions().get::<TcpConnectInfo>().and_then(TcpConnectInfo::remote_addr), None),
+        };
+        let mut parts = self.service.parts(request.headers().clone(), remote_addr, peer_certs);
+        parts.method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let (_, decision) = limiter.acquire(&mut parts, &path).await;
+            if !decision.allowed {
+                return Ok(Status::from(CustomError::TooManyRequests).to_http());
+            }
+            inner.call(request).await
+        })
+    }
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<EmployeeEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Rpc for EmployeeService {
+    type WatchStream = EventStream;
+
+    #[tracing::instrument(name = "grpc.get", skip_all, fields(employee_id = request.get_ref().id))]
+    async fn get(&self, request: Request<GetEmployeeRequest>) -> Result<Response<Employee>, Status> {
+        let caller = self.caller(&request).await?;
+        let GetEmployeeRequest { id, as_of } = request.into_inner();
+        let tenant = &caller.tenant;
+
+        let cached = match as_of {
+            None if caller.read_source.may_read_cache() => self.cache.get(tenant.id, id).await,
+            _ => None,
+        };
+
+        let profile = match (cached, as_of) {
+            (Some(profile), _) => profile,
+            (None, Some(as_of)) => {
+                let mut tx = tenant.begin(&caller.read_pool).await?;
+                views::find_profile_as_of(&mut tx, tenant, id, to_datetime(&as_of)?).await?
+            }
+            (None, None) => {
+                let mut tx = tenant.begin(&caller.read_pool).await?;
+                // Merged employees are found under the record they were merged into.
+                let profile = match views::find_profile(&mut tx, tenant, id).await {
+                    Err(CustomError::TaskNotFound) => {
+                        let survivor = merges::survivor(&mut tx, tenant, id).await?.ok_or(CustomError::TaskNotFound)?;
+                        views::find_profile(&mut tx, tenant, survivor).await?
+                    }
+                    profile => profile?,
+                };
+                if caller.read_source.may_fill_cache() {
+                    self.cache.put(tenant.id, &profile).await;
+                }
+                profile
+            }
+        };
+
+        Ok(Response::new(self.employee(&caller, profile)?))
+    }
+
+    #[tracing::instrument(name = "grpc.list", skip_all)]
+    async fn list(&self, request: Request<ListEmployeesRequest>) -> Result<Response<ListEmployeesResponse>, Status> {
+        let caller = self.caller(&request).await?;
+        let request = request.into_inner();
+
+        let filter = ProfileFilter {
+            eemail: request.eemail,
+            econtact: request.econtact,
+            status: request.status.as_deref().map(to_status).transpose()?,
+            as_of: request.as_of.as_ref().map(to_datetime).transpose()?,
+            sort: request.sort,
+            params: request.custom.into_iter().map(|(name, value)| (format!("custom.{name}"), value)).collect(),
+        };
+
+        let mut tx = caller.tenant.begin(&caller.read_pool).await?;
+        let profiles = views::find_profiles(&mut tx, &caller.tenant, &self.pii, &filter).await?;
+
+        let mut employees = Vec::with_capacity(profiles.len());
+        for profile in profiles {
+            employees.push(self.employee(&caller, profile)?);
+        }
+
+        Ok(Response::new(ListEmployeesResponse { employees }))
+    }
+
+    #[tracing::instrument(name = "grpc.create", skip_all, fields(employee_id = request.get_ref().id))]
+    async fn create(&self, request: Request<Employee>) -> Result<Response<Employee>, Status> {
+        let caller = self.caller(&request).await?;
+        let data = to_new_profile(request.into_inner());
+
+        let mut tx = caller.tenant.begin(&self.pool).await?;
+        views::insert_profile(&mut tx, &caller.tenant, &self.pii, &data).await?;
+        let profile = views::find_profile(&mut tx, &caller.tenant, data.id).await?;
+        tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+
+        Ok(Response::new(self.employee(&caller, profile)?))
+    }
+
+    #[tracing::instrument(name = "grpc.update", skip_all, fields(employee_id = request.get_ref().id))]
+    async fn update(&self, request: Request<UpdateEmployeeRequest>) -> Result<Response<Employee>, Status> {
+        let caller = self.caller(&request).await?;
+        let UpdateEmployeeRequest { id, employee } = request.into_inner();
+        let data = to_new_profile(employee.ok_or(CustomError::BadRequest)?);
+
+        let mut tx = caller.tenant.begin(&self.pool).await?;
+        let id = views::modify_profile(&mut tx, &caller.tenant, &self.pii, id, &data).await?;
+        let profile = views::find_profile(&mut tx, &caller.tenant, id).await?;
+        tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+        self.cache.invalidate(caller.tenant.id, id).await;
+
+        Ok(Response::new(self.employee(&caller, profile)?))
+    }
+
+    #[tracing::instrument(name = "grpc.delete", skip_all, fields(employee_id = request.get_ref().id))]
+    async fn delete(&self, request: Request<DeleteEmployeeRequest>) -> Result<Response<DeleteEmployeeResponse>, Status> {
+        let caller = self.caller(&request).await?;
+        let id = request.into_inner().id;
+
+        let mut tx = caller.tenant.begin(&self.pool).await?;
+        let id = views::remove_profile(&mut tx, &caller.tenant, id).await?;
+        tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+        self.cache.invalidate(caller.tenant.id, id).await;
+
+        Ok(Response::new(DeleteEmployeeResponse {}))
+    }
+
+    // Each event carries the employee as it is when the event is sent, read
+    // from the primary. A watcher that falls too far behind is disconnected
+    // with RESOURCE_EXHAUSTED and should list again before resuming.
+    #[tracing::instrument(name = "grpc.watch", skip_all)]
+    async fn watch(&self, request: Request<WatchEmployeesRequest>) -> Result<Response<Self::WatchStream>, Status> {
+        let caller = self.caller(&request).await?;
+        let only = request.into_inner().id;
+        let mut changes = self.changes.subscribe();
+        let (events, stream) = mpsc::channel(16);
+        let service = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                // Stops as soon as the client goes away rather than at the next change.
+                let received = tokio::select! {
+                    received = changes.recv() => received,
+                    _ = events.closed() => return,
+                };
+                let change = match received {
+                    Ok(change) => change,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        _ = events.send(Err(Status::resource_exhausted(format!("missed {missed} changes")))).await;
+                        return;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                if !change.watched_by(caller.tenant.id, only) {
+                    continue;
+                }
+
+                let event = match service.event(&caller, &change).await {
+                    Ok(Some(event)) => Ok(event),
+                    Ok(None) => continue,
+                    Err(status) => Err(status),
+                };
+                if events.send(event).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(stream)) as Self::WatchStream))
+    }
+}
+
+impl EmployeeService {
+    // None when the employee is gone again by the time the event is built;
+    // its deletion follows.
+    async fn event(&self, caller: &Caller, change: &Change) -> Result<Option<EmployeeEvent>, Status> {
+        let kind = match change.op.as_str() {
+            "insert" => Kind::Created,
+            "update" => Kind::Updated,
+            _ => return Ok(Some(EmployeeEvent { kind: Kind::Deleted.into(), id: change.id, employee: None })),
+        };
+
+        let mut tx = caller.tenant.begin(&self.pool).await?;
+        let profile = match views::find_profile(&mut tx, &caller.tenant, change.id).await {
+            Ok(profile) => profile,
+            Err(CustomError::TaskNotFound) => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(Some(EmployeeEvent { kind: kind.into(), id: change.id, employee: Some(self.employee(caller, profile)?) }))
+    }
+}
+
+fn to_employee(profile: Profile) -> Employee {
+    Employee {
+        id: profile.id,
+        eid: profile.eid,
+        ename: profile.ename,
+        eemail: profile.eemail,
+        econtact: profile.econtact,
+        status: profile.status.as_str().to_string(),
+        custom: Some(to_struct(&profile.custom)),
+        eemail_verified_at: profile.eemail_verified_at.map(to_timestamp),
+        econtact_verified_at: profile.econtact_verified_at.map(to_timestamp),
+    }
+}
+
+// Status and verification times are managed by their own endpoints, as with
+// REST writes.
+fn to_new_profile(employee: Employee) -> NewProfile {
+    NewProfile {
+        id: employee.id,
+        eid: employee.eid,
+        ename: employee.ename,
+        eemail: employee.eemail,
+        econtact: employee.econtact,
+        custom: employee
+            .custom
+            .map(|custom| custom.fields.into_iter().map(|(name, value)| (name, from_value(value))).collect()),
+    }
+}
+
+fn to_status(status: &str) -> Result<EmployeeStatus, CustomError> {
+    EmployeeStatus::from_str(status, false).map_err(|_| CustomError::BadRequest)
+}
+
+fn to_timestamp(time: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp { seconds: time.timestamp(), nanos: time.timestamp_subsec_nanos() as i32 }
+}
+
+fn to_datetime(timestamp: &prost_types::Timestamp) -> Result<DateTime<Utc>, CustomError> {
+    u32::try_from(timestamp.nanos)
+        .ok()
+        .and_then(|nanos| Utc.timestamp_opt(timestamp.seconds, nanos).single())
+        .ok_or(CustomError::BadRequest)
+}
+
+fn to_struct(map: &Map<String, Value>) -> prost_types::Struct {
+    prost_types::Struct { fields: map.iter().map(|(name, value)| (name.clone(), to_value(value))).collect() }
+}
+
+fn to_value(value: &Value) -> prost_types::Value {
+    use prost_types::{value::Kind, ListValue};
+
+    let kind = match value {
+        Value::Null => Kind::NullValue(0),
+        Value::Bool(flag) => Kind::BoolValue(*flag),
+        Value::Number(number) => Kind::NumberValue(number.as_f64().unwrap_or_default()),
+        Value::String(text) => Kind::StringValue(text.clone()),
+        Value::Array(items) => Kind::ListValue(ListValue { values: items.iter().map(to_value).collect() }),
+        Value::Object(map) => Kind::StructValue(to_struct(map)),
+    };
+    prost_types::Value { kind: Some(kind) }
+}
+
+// Protobuf numbers are all doubles; whole ones become integers so integer
+// custom fields accept them.
+fn from_value(value: prost_types::Value) -> Value {
+    use prost_types::value::Kind;
+
+    match value.kind {
+        None | Some(Kind::NullValue(_)) => Value::Null,
+        Some(Kind::BoolValue(flag)) => Value::Bool(flag),
+        Some(Kind::NumberValue(number)) if number.fract() == 0.0 && number.abs() < i64::MAX as f64 => Value::from(number as i64),
+        Some(Kind::NumberValue(number)) => Number::from_f64(number).map_or(Value::Null, Value::Number),
+        Some(Kind::StringValue(text)) => Value::String(text),
+        Some(Kind::ListValue(list)) => Value::Array(list.values.into_iter().map(from_value).collect()),
+        Some(Kind::StructValue(map)) => Value::Object(map.fields.into_iter().map(|(name, value)| (name, from_value(value))).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::value::Kind as ValueKind;
+    use serde_json::json;
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    fn number(value: f64) -> prost_types::Value {
+        prost_types::Value { kind: Some(ValueKind::NumberValue(value)) }
+    }
+
+    // A service on a pool that connects on first use, so tests that never
+    // reach the database run without one.
+    fn service(config: Config) -> EmployeeService {
+        let pool = PgPoolOptions::new().connect_lazy(&config.database_url).unwrap();
+        let replicas = Arc::new(Replicas::new(pool.clone(), &[], Duration::from_secs(5)).unwrap());
+        let pii = Arc::new(Pii::from_config(&config).unwrap());
+        let cache = Arc::new(ProfileCache::new(None, Duration::ZERO));
+        let (changes, _) = broadcast::channel(1);
+        EmployeeService { config: Arc::new(config), pool, replicas, pii, cache, changes }
+    }
+
+    #[test]
+    fn whole_numbers_become_integers() {
+        assert!(from_value(number(3.0)).is_i64());
+        assert_eq!(from_value(number(-42.0)), json!(-42));
+        assert_eq!(from_value(number(2.5)), json!(2.5));
+        // Too large for an i64, so it stays a float.
+        assert!(from_value(number(1e20)).is_f64());
+        assert_eq!(from_value(number(f64::NAN)), Value::Null);
+    }
+
+    #[test]
+    fn nested_values_convert_throughout() {
+        let list = prost_types::ListValue { values: vec![number(1.0), number(0.5)] };
+        let map = to_struct(&Map::from_iter([("flag".to_string(), json!(true)), ("name".to_string(), json!("Ada"))]));
+        let fields = [
+            ("list".to_string(), prost_types::Value { kind: Some(ValueKind::ListValue(list)) }),
+            ("map".to_string(), prost_types::Value { kind: Some(ValueKind::StructValue(map)) }),
+        ];
+        let value = prost_types::Value { kind: Some(ValueKind::StructValue(prost_types::Struct { fields: fields.into() })) };
+
+        assert_eq!(from_value(value), json!({"list": [1, 0.5], "map": {"flag": true, "name": "Ada"}}));
+    }
+
+    #[test]
+    fn timestamps_round_trip() {
+        let time = Utc.timestamp_opt(1_700_000_000, 123_456_789).unwrap();
+        assert_eq!(to_datetime(&to_timestamp(time)).unwrap(), time);
+    }
+
+    #[test]
+    fn rejects_negative_nanos() {
+        let timestamp = prost_types::Timestamp { seconds: 1_700_000_000, nanos: -1 };
+        assert!(matches!(to_datetime(&timestamp), Err(CustomError::BadRequest)));
+    }
+
+    #[test]
+    fn missing_custom_values_are_kept() {
+        let employee = Employee { id: 7, ename: "Ada".to_string(), ..Default::default() };
+        let data = to_new_profile(employee.clone());
+        assert_eq!((data.id, data.ename.as_str()), (7, "Ada"));
+        assert!(data.custom.is_none());
+
+        let custom = to_struct(&Map::from_iter([("level".to_string(), json!(3))]));
+        let data = to_new_profile(Employee { custom: Some(custom), ..employee });
+        assert_eq!(data.custom, Some(Map::from_iter([("level".to_string(), json!(3))])));
+    }
+
+    #[test]
+    fn watch_sees_only_its_tenant_and_employee() {
+        let change = Change { op: "update".to_string(), tenant_id: 1, id: 7 };
+        assert!(change.watched_by(1, None));
+        assert!(change.watched_by(1, Some(7)));
+        assert!(!change.watched_by(1, Some(8)));
+        assert!(!change.watched_by(2, None));
+    }
+
+    #[tokio::test]
+    async fn parts_carry_the_connection() {
+        let service = service(Config::load().unwrap());
+        let cert = Certificate::from_pem(b"not a certificate");
+        let addr = SocketAddr::from(([10, 0, 0, 1], 4000));
+        let mut request = Request::new(());
+        request.metadata_mut().insert("x-api-key", "secret".parse().unwrap());
+
+        let parts = service.parts(request.metadata().clone().into_headers(), Some(addr), Some(Arc::new(vec![cert.clone()])));
+
+        assert_eq!(parts.headers["x-api-key"], "secret");
+        assert_eq!(parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|info| info.0), Some(addr));
+        let identity = parts.extensions.get::<Option<ClientIdentity>>().cloned().flatten().unwrap();
+        assert_eq!(identity.fingerprint, ClientIdentity::from_der(cert.get_ref()).fingerprint);
+
+        let parts = service.parts(http::HeaderMap::new(), None, None);
+        assert!(matches!(parts.extensions.get::<Option<ClientIdentity>>(), Some(None)));
+    }
+
+    #[tokio::test]
+    async fn caller_rejections_become_status_codes() {
+        let mut config = Config::load().unwrap();
+        config.default_tenant = None;
+        config.jwt_secret = Some("secret".to_string());
+        let service = service(config);
+
+        // Anonymous, with no default tenant to fall back to.
+        let status = service.caller(&Request::new(())).await.err().unwrap();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let mut request = Request::new(());
+        request.metadata_mut().insert("authorization", "Bearer not.a.token".parse().unwrap());
+        let status = service.caller(&request).await.err().unwrap();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn caller_resolves_the_tenant_and_access() {
+        let mut config = Config::load().unwrap();
+        config.admin_api_key = Some("secret-admin".to_string());
+        let Some(slug) = config.default_tenant.clone() else { return };
+        let service = service(config);
+        let Ok(tenant_id) = crate::tenants::tenant_id(&service.pool, &slug).await else {
+            eprintln!("skipping: database unavailable");
+            return;
+        };
+
+        let caller = service.caller(&Request::new(())).await.unwrap();
+        assert_eq!(caller.tenant.id, tenant_id);
+        assert!(!caller.access.0);
+        assert!(matches!(caller.read_source, ReadSource::Primary));
+
+        let mut request = Request::new(());
+        request.metadata_mut().insert("x-api-key", "secret-admin".parse().unwrap());
+        let caller = service.caller(&request).await.unwrap();
+        assert_eq!(caller.tenant.id, tenant_id);
+        assert!(caller.access.0);
+    }
+}
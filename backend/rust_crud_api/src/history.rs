@@ -0,0 +1,111 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use sqlx::PgConnection;
+use tracing::Instrument;
+
+use crate::{
+    errors::CustomError,
+    logging::query_span,
+    models::Profile,
+    pii::{Pii, PiiAccess},
+    replicas::ReadPool,
+    tenants::Tenant,
+    views,
+};
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub from: DateTime<Utc>,
+    // Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+}
+
+// One stored version of an employee and the period it was current for.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Version {
+    pub valid_from: DateTime<Utc>,
+    pub valid_to: Option<DateTime<Utc>>,
+    // The id of a record merged into this one that the version belonged to.
+    pub merged_from: Option<i32>,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub profile: Profile,
+}
+
+#[derive(Serialize)]
+pub struct Change {
+    pub field: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+#[derive(Serialize)]
+pub struct ProfileDiff {
+    pub employee_id: i32,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub changes: Vec<Change>,
+}
+
+// The fields of one employee that differ between two points in time. Fields
+// are null on a side where the employee did not exist yet or any more.
+#[tracing::instrument(name = "history.diff", skip_all, fields(employee_id = id))]
+pub async fn diff(Path(id): Path<i32>, Query(range): Query<DiffQuery>, tenant: Tenant, access: PiiAccess, Extension(pii): Extension<Arc<Pii>>, ReadPool(pool, _): ReadPool) -> Result<Json<ProfileDiff>, CustomError> {
+    let to = range.to.unwrap_or_else(Utc::now);
+    let mut tx = tenant.begin(&pool).await?;
+    let id = views::resolve_id(&mut tx, &tenant, id).await?;
+    let before = views::find_profile_as_of(&mut tx, &tenant, id, range.from).await.ok();
+    let after = views::find_profile_as_of(&mut tx, &tenant, id, to).await.ok();
+    if before.is_none() && after.is_none() {
+        return Err(CustomError::TaskNotFound);
+    }
+
+    let before = fields(before, &pii, &access)?;
+    let after = fields(after, &pii, &access)?;
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let changes = names
+        .into_iter()
+        .filter(|name| before.get(*name) != after.get(*name))
+        .map(|name| Change {
+            field: name.clone(),
+            from: before.get(name).cloned().unwrap_or(Value::Null),
+            to: after.get(name).cloned().unwrap_or(Value::Null),
+        })
+        .collect();
+
+    Ok(Json(ProfileDiff { employee_id: id, from: range.from, to, changes }))
+}
+
+fn fields(profile: Option<Profile>, pii: &Pii, access: &PiiAccess) -> Result<Map<String, Value>, CustomError> {
+    let Some(mut profile) = profile else {
+        return Ok(Map::new());
+    };
+
+    pii.reveal(&mut profile, access)?;
+    match serde_json::to_value(profile) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        _ => Err(CustomError::InternalServerError),
+    }
+}
+
+// Every stored version of the employee, and of records merged into it, oldest
+// first.
+pub async fn versions(conn: &mut PgConnection, tenant: &Tenant, id: i32) -> Result<Vec<Version>, CustomError> {
+    let sql = format!("SELECT valid_from, valid_to, merged_from, {} FROM employee_history WHERE employee_id=$1 AND tenant_id=$2 ORDER BY valid_from, history_id", views::HISTORY_ROW);
+    sqlx::query_as(&sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_all(conn)
+    .instrument(query_span(&sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })
+}
@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes, Full},
+    extract::FromRequestParts,
+    http::{header::CONTENT_TYPE, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use hyper::body::HttpBody;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::{config::Config, errors::CustomError, logging::query_span, pii::Pii, tenants::Tenant};
+
+// The largest request body remembered, the same limit axum applies to bodies
+// its extractors read.
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(sqlx::FromRow)]
+struct StoredResponse {
+    fingerprint: String,
+    status: Option<i16>,
+    content_type: Option<String>,
+    body: Option<String>,
+}
+
+// The employees a response describes, added to the responses of idempotent
+// routes. Stored responses are dropped when one of them is erased.
+#[derive(Clone)]
+pub struct Subjects(pub Vec<i32>);
+
+// Makes POST requests carrying an `Idempotency-Key` header safe to retry: the
+// first response is stored, encrypted since it may carry contact data, and
+// replayed for repeats of the same request within the configured window.
+pub async fn idempotent(
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pii): Extension<Arc<Pii>>,
+    Extension(pool): Extension<PgPool>,
+    req: Request<Body>,
+    next: Next<Body>,
+) -> Result<Response, CustomError> {
+    let key = match req.headers().get("Idempotency-Key") {
+        Some(key) if req.method() == Method::POST => key.to_str().map_err(|_| CustomError::BadRequest)?.to_string(),
+        _ => return Ok(next.run(req).await),
+    };
+    if key.is_empty() || key.len() > 255 {
+        return Err(CustomError::BadRequest);
+    }
+
+    let (mut parts, body) = req.into_parts();
+    let tenant = Tenant::from_request_parts(&mut parts, &()).await?;
+    let body = read_body(body, MAX_BODY_BYTES).await?;
+
+    // The same body means something else as JSON than as CSV or XML.
+    let mut hasher = Sha256::new();
+    for part in [parts.method.as_str().as_bytes(), parts.uri.path().as_bytes(), parts.headers.get(CONTENT_TYPE).map_or(b"", |v| v.as_bytes())] {
+        hasher.update(part);
+        hasher.update(b"\n");
+    }
+    hasher.update(&body);
+    let fingerprint = hex::encode(hasher.finalize());
+
+    let claimed = claim(&pool, tenant.id, &key, &fingerprint, config.idempotency_ttl_secs, config.idempotency_lease_secs).await?;
+    if !claimed {
+        let sql = "SELECT fingerprint, status, content_type, body FROM idempotency_key WHERE tenant_id=$1 AND key=$2";
+        let stored: StoredResponse = sqlx::query_as(sql)
+        .bind(tenant.id)
+        .bind(&key)
+        .fetch_one(&pool)
+        .instrument(query_span(sql, None))
+        .await.map_err(|_| {
+            CustomError::InternalServerError
+        })?;
+
+        return replay(stored, &fingerprint, &pii);
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await.map_err(|_| CustomError::InternalServerError)?;
+
+    // Server errors are not remembered so the client can retry them.
+    let outcome = if parts.status.is_server_error() {
+        let sql = "DELETE FROM idempotency_key WHERE tenant_id=$1 AND key=$2";
+        sqlx::query(sql)
+        .bind(tenant.id)
+        .bind(&key)
+        .execute(&pool)
+        .instrument(query_span(sql, None))
+        .await
+    } else {
+        let sql = "UPDATE idempotency_key SET status=$1, content_type=$2, body=$3, employee_ids=$4 WHERE tenant_id=$5 AND key=$6";
+        sqlx::query(sql)
+        .bind(parts.status.as_u16() as i16)
+        .bind(parts.headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()))
+        .bind(pii.seal(&body))
+        .bind(parts.extensions.get::<Subjects>().map(|subjects| subjects.0.clone()).unwrap_or_default())
+        .bind(tenant.id)
+        .bind(&key)
+        .execute(&pool)
+        .instrument(query_span(sql, None))
+        .await
+    };
+    outcome.map_err(|_| CustomError::InternalServerError)?;
+
+    Ok(Response::from_parts(parts, axum::body::boxed(Full::from(body))))
+}
+
+// Reads the whole body, refusing one larger than `limit`.
+async fn read_body(mut body: Body, limit: usize) -> Result<Bytes, CustomError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| CustomError::BadRequest)?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(CustomError::PayloadTooLarge);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes.into())
+}
+
+// Records the key as in progress. Returns false when another request already
+// holds it; an expired entry is taken over, as is a claim left unanswered for
+// longer than the lease by a request that never finished.
+async fn claim(pool: &PgPool, tenant_id: i32, key: &str, fingerprint: &str, ttl_secs: i64, lease_secs: i64) -> Result<bool, CustomError> {
+    let sql = "INSERT INTO idempotency_key (tenant_id, key, fingerprint) values ($1, $2, $3) \
+               ON CONFLICT (tenant_id, key) DO UPDATE SET fingerprint=EXCLUDED.fingerprint, status=NULL, content_type=NULL, body=NULL, created_at=now() \
+               WHERE idempotency_key.created_at < now() - make_interval(secs => $4) \
+               OR (idempotency_key.status IS NULL AND idempotency_key.created_at < now() - make_interval(secs => $5)) \
+               RETURNING tenant_id";
+    let claimed: Option<i32> = sqlx::query_scalar(sql)
+    .bind(tenant_id)
+    .bind(key)
+    .bind(fingerprint)
+    .bind(ttl_secs as f64)
+    .bind(lease_secs as f64)
+    .fetch_optional(pool)
+    .instrument(query_span(sql, None))
+    .await
+    .map_err(|_| CustomError::InternalServerError)?;
+
+    Ok(claimed.is_some())
+}
+
+fn replay(stored: StoredResponse, fingerprint: &str, pii: &Pii) -> Result<Response, CustomError> {
+    if stored.fingerprint != fingerprint {
+        return Err(CustomError::UnprocessableEntity);
+    }
+
+    let Some(status) = stored.status else {
+        return Err(CustomError::Conflict);
+    };
+
+    let status = StatusCode::from_u16(status as u16).map_err(|_| CustomError::InternalServerError)?;
+    let body = stored.body.map(|body| pii.open(&body)).transpose()?.unwrap_or_default();
+    let mut response = (status, Bytes::from(body)).into_response();
+    let headers = response.headers_mut();
+    if let Some(content_type) = stored.content_type.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    headers.insert("Idempotent-Replayed", HeaderValue::from_static("true"));
+
+    Ok(response)
+}
@@ -0,0 +1,237 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::{api_keys::GlobalAdmin, config::Config, errors::CustomError, logging::query_span};
+
+// A run still marked running after this long is assumed to belong to an
+// instance that died, and is handed to another worker.
+const STALE_AFTER_SECS: f64 = 60.0 * 60.0;
+
+const RUN_COLUMNS: &str = "id, job, status, run_at, triggered_by, started_at, finished_at, error, created_at";
+
+// Periodic maintenance work. Every instance schedules and works the queue in
+// job_run: scheduled runs are enqueued once however many instances see them
+// due, and each queued run is claimed by a single worker.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Job {
+    PurgeIdempotencyKeys,
+    PurgeJobRuns,
+}
+
+impl Job {
+    pub const ALL: [Job; 2] = [Job::PurgeIdempotencyKeys, Job::PurgeJobRuns];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Job::PurgeIdempotencyKeys => "purge_idempotency_keys",
+            Job::PurgeJobRuns => "purge_job_runs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Job> {
+        Job::ALL.into_iter().find(|job| job.name() == name)
+    }
+
+    // Cron expressions with a seconds field, in UTC.
+    pub fn schedule(self) -> &'static str {
+        match self {
+            Job::PurgeIdempotencyKeys => "0 0 * * * *",
+            Job::PurgeJobRuns => "0 30 3 * * *",
+        }
+    }
+
+    // Does the work and returns the number of rows it affected.
+    async fn run(self, pool: &PgPool, config: &Config) -> Result<u64, sqlx::Error> {
+        let (sql, secs) = match self {
+            Job::PurgeIdempotencyKeys => (
+                "DELETE FROM idempotency_key WHERE created_at < now() - make_interval(secs => $1)",
+                config.idempotency_ttl_secs as f64,
+            ),
+            Job::PurgeJobRuns => (
+                "DELETE FROM job_run WHERE finished_at < now() - make_interval(secs => $1)",
+                (config.job_run_retention_days * 24 * 60 * 60) as f64,
+            ),
+        };
+
+        let result = sqlx::query(sql).bind(secs).execute(pool).instrument(query_span(sql, None)).await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct JobRun {
+    pub id: i64,
+    pub job: String,
+    pub status: String,
+    pub run_at: DateTime<Utc>,
+    pub triggered_by: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Starts the scheduler and queue worker for this instance. Does nothing when
+// JOB_POLL_SECS is 0, so instances can be kept out of job work; runs
+// triggered through the API then wait for an instance that polls.
+pub fn start(pool: PgPool, config: Arc<Config>) -> anyhow::Result<()> {
+    if config.job_poll_secs == 0 {
+        return Ok(());
+    }
+
+    let mut schedules = Job::ALL
+        .into_iter()
+        .map(|job| Ok((job, Schedule::from_str(job.schedule())?, None)))
+        .collect::<Result<Vec<(Job, Schedule, Option<DateTime<Utc>>)>, cron::error::Error>>()?;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.job_poll_secs));
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+
+            for (job, schedule, next) in &mut schedules {
+                // Runs missed while this instance was not polling are skipped,
+                // only the latest one due is enqueued.
+                if let Some(due) = next.filter(|due| *due <= now) {
+                    if let Err(error) = enqueue(&pool, *job, due, None).await {
+                        tracing::warn!("could not enqueue job {}: {error}", job.name());
+                    }
+                }
+                if next.is_none_or(|due| due <= now) {
+                    *next = schedule.after(&now).next();
+                }
+            }
+
+            loop {
+                match claim(&pool).await {
+                    Ok(Some(run)) => execute(&pool, &config, run).await,
+                    Ok(None) => break,
+                    Err(error) => {
+                        tracing::warn!("could not claim a job run: {error}");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn enqueue(pool: &PgPool, job: Job, run_at: DateTime<Utc>, triggered_by: Option<&str>) -> Result<Option<JobRun>, sqlx::Error> {
+    let sql = format!("INSERT INTO job_run (job, run_at, triggered_by) values ($1, $2, $3) ON CONFLICT DO NOTHING RETURNING {RUN_COLUMNS}");
+    sqlx::query_as(&sql)
+    .bind(job.name())
+    .bind(run_at)
+    .bind(triggered_by)
+    .fetch_optional(pool)
+    .instrument(query_span(&sql, None))
+    .await
+}
+
+// Takes the oldest due run. SKIP LOCKED lets workers on other instances pass
+// over a run being claimed here instead of waiting for it.
+async fn claim(pool: &PgPool) -> Result<Option<JobRun>, sqlx::Error> {
+    let sql = format!(
+        "UPDATE job_run SET status='running', started_at=now(), error=NULL WHERE id = (\
+         SELECT id FROM job_run \
+         WHERE (status='queued' AND run_at <= now()) OR (status='running' AND started_at < now() - make_interval(secs => $1)) \
+         ORDER BY run_at, id FOR UPDATE SKIP LOCKED LIMIT 1) \
+         RETURNING {RUN_COLUMNS}"
+    );
+    sqlx::query_as(&sql)
+    .bind(STALE_AFTER_SECS)
+    .fetch_optional(pool)
+    .instrument(query_span(&sql, None))
+    .await
+}
+
+async fn execute(pool: &PgPool, config: &Config, run: JobRun) {
+    let span = tracing::info_span!("job.run", job = %run.job, run_id = run.id);
+    let outcome = match Job::from_name(&run.job) {
+        Some(job) => job.run(pool, config).instrument(span.clone()).await.map_err(|error| error.to_string()),
+        None => Err(format!("unknown job {:?}", run.job)),
+    };
+
+    let (status, error) = match outcome {
+        Ok(rows) => {
+            span.in_scope(|| tracing::info!(rows, "job succeeded"));
+            ("succeeded", None)
+        }
+        Err(error) => {
+            span.in_scope(|| tracing::warn!("job failed: {error}"));
+            ("failed", Some(error))
+        }
+    };
+
+    let sql = "UPDATE job_run SET status=$1, error=$2, finished_at=now() WHERE id=$3";
+    if let Err(error) = sqlx::query(sql).bind(status).bind(error).bind(run.id).execute(pool).instrument(query_span(sql, None)).await {
+        tracing::warn!("could not record the outcome of job run {}: {error}", run.id);
+    }
+}
+
+#[derive(Serialize)]
+pub struct JobInfo {
+    pub name: &'static str,
+    pub schedule: &'static str,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+pub async fn list_jobs(_: GlobalAdmin) -> Result<Json<Vec<JobInfo>>, CustomError> {
+    let jobs = Job::ALL
+        .into_iter()
+        .map(|job| JobInfo {
+            name: job.name(),
+            schedule: job.schedule(),
+            next_run_at: Schedule::from_str(job.schedule()).ok().and_then(|schedule| schedule.upcoming(Utc).next()),
+        })
+        .collect();
+
+    Ok(Json(jobs))
+}
+
+#[derive(Deserialize)]
+pub struct RunFilter {
+    pub job: Option<String>,
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+// Most recent runs first.
+pub async fn list_runs(_: GlobalAdmin, Query(filter): Query<RunFilter>, Extension(pool): Extension<PgPool>) -> Result<Json<Vec<JobRun>>, CustomError> {
+    let sql = format!("SELECT {RUN_COLUMNS} FROM job_run WHERE ($1::text IS NULL OR job=$1) AND ($2::text IS NULL OR status=$2) ORDER BY id DESC LIMIT $3");
+    let runs = sqlx::query_as(&sql)
+    .bind(filter.job)
+    .bind(filter.status)
+    .bind(filter.limit.unwrap_or(50).clamp(1, 500))
+    .fetch_all(&pool)
+    .instrument(query_span(&sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    Ok(Json(runs))
+}
+
+// Queues a run now; it starts at the next poll of any instance.
+pub async fn trigger_job(GlobalAdmin(key): GlobalAdmin, Path(name): Path<String>, Extension(pool): Extension<PgPool>) -> Result<(StatusCode, Json<JobRun>), CustomError> {
+    let job = Job::from_name(&name).ok_or(CustomError::TaskNotFound)?;
+    let run = enqueue(&pool, job, Utc::now(), Some(&key.name))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?
+    .ok_or(CustomError::InternalServerError)?;
+
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::{
+    http::{header, HeaderName, HeaderValue, Method},
+    Router,
+};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, CorsLayer},
+    set_header::SetResponseHeaderLayer,
+};
+
+use crate::config::Config;
+
+// Request headers browsers may send cross-origin.
+const ALLOWED_HEADERS: [HeaderName; 6] = [
+    header::AUTHORIZATION,
+    header::CONTENT_TYPE,
+    HeaderName::from_static("x-api-key"),
+    HeaderName::from_static("x-tenant-id"),
+    HeaderName::from_static("idempotency-key"),
+    HeaderName::from_static("x-request-id"),
+];
+
+// Response headers scripts on other origins may read.
+const EXPOSED_HEADERS: [HeaderName; 9] = [
+    header::RETRY_AFTER,
+    header::LINK,
+    HeaderName::from_static("ratelimit-limit"),
+    HeaderName::from_static("ratelimit-remaining"),
+    HeaderName::from_static("ratelimit-reset"),
+    HeaderName::from_static("deprecation"),
+    HeaderName::from_static("sunset"),
+    HeaderName::from_static("idempotent-replayed"),
+    HeaderName::from_static("x-request-id"),
+];
+
+// Wraps the application in the CORS, compression and security header layers.
+pub fn apply(router: Router, config: &Config) -> anyhow::Result<Router> {
+    let mut router = router
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        ))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::X_FRAME_OPTIONS,
+            HeaderValue::from_static("DENY"),
+        ))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::REFERRER_POLICY,
+            HeaderValue::from_static("no-referrer"),
+        ));
+
+    if let Some(csp) = &config.content_security_policy {
+        let value = HeaderValue::from_str(csp).context("Invalid CONTENT_SECURITY_POLICY")?;
+        router = router.layer(SetResponseHeaderLayer::if_not_present(header::CONTENT_SECURITY_POLICY, value));
+    }
+
+    if config.hsts_max_age_secs > 0 {
+        let value = HeaderValue::from_str(&format!("max-age={}; includeSubDomains", config.hsts_max_age_secs))?;
+        router = router.layer(SetResponseHeaderLayer::if_not_present(header::STRICT_TRANSPORT_SECURITY, value));
+    }
+
+    if !config.compression.is_empty() {
+        let enabled = |name: &str| config.compression.iter().any(|algorithm| algorithm == name);
+        router = router.layer(
+            CompressionLayer::new()
+                .gzip(enabled("gzip"))
+                .br(enabled("br"))
+                .zstd(enabled("zstd"))
+                .no_deflate(),
+        );
+    }
+
+    if !config.cors_allowed_origins.is_empty() {
+        router = router.layer(cors(config)?);
+    }
+
+    Ok(router)
+}
+
+fn cors(config: &Config) -> anyhow::Result<CorsLayer> {
+    let wildcard = config.cors_allowed_origins.iter().any(|origin| origin == "*");
+    anyhow::ensure!(
+        !(wildcard && config.cors_allow_credentials),
+        "CORS_ALLOW_CREDENTIALS cannot be combined with a wildcard CORS_ALLOWED_ORIGINS"
+    );
+
+    let origins = if wildcard {
+        AllowOrigin::any()
+    } else {
+        let origins = config
+            .cors_allowed_origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Invalid CORS_ALLOWED_ORIGINS")?;
+        AllowOrigin::list(origins)
+    };
+
+    let methods = config
+        .cors_allowed_methods
+        .iter()
+        .map(|method| method.parse::<Method>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid CORS_ALLOWED_METHODS")?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(ALLOWED_HEADERS)
+        .expose_headers(EXPOSED_HEADERS)
+        .allow_credentials(config.cors_allow_credentials)
+        .max_age(Duration::from_secs(config.cors_max_age_secs)))
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::{extract::Extension, middleware, Router};
+use sqlx::PgPool;
+use tower_http::{
+    request_id::{PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+
+pub mod api_keys;
+pub mod batch;
+pub mod cache;
+pub mod cli;
+pub mod config;
+pub mod custom_fields;
+pub mod errors;
+pub mod gdpr;
+pub mod grpc;
+pub mod history;
+pub mod idempotency;
+pub mod jobs;
+pub mod layers;
+pub mod lifecycle;
+pub mod logging;
+pub mod merges;
+pub mod models;
+pub mod negotiate;
+pub mod notify;
+pub mod pii;
+pub mod rate_limit;
+pub mod replicas;
+pub mod routes;
+pub mod seed;
+pub mod stats;
+pub mod tenants;
+pub mod tls;
+pub mod tokens;
+pub mod verification;
+pub mod views;
+
+// The services shared by the HTTP and gRPC servers.
+pub struct Services {
+    pub replicas: Arc<replicas::Replicas>,
+    pub pii: Arc<pii::Pii>,
+    pub notifier: Arc<dyn notify::Notifier>,
+    pub limiter: Arc<rate_limit::RateLimiter>,
+    pub cache: Arc<cache::ProfileCache>,
+}
+
+impl Services {
+    pub fn new(config: &config::Config, pool: PgPool) -> anyhow::Result<Self> {
+        let replicas = Arc::new(replicas::Replicas::new(
+            pool,
+            &config.database_replica_urls,
+            Duration::from_secs(config.replica_sticky_secs),
+        )
+        .context("Invalid DATABASE_REPLICA_URLS")?);
+
+        let pii = Arc::new(pii::Pii::from_config(config).context("Invalid PII encryption settings")?);
+
+        let notifier = notify::from_config(config).context("Invalid NOTIFIER")?;
+
+        let limiter = Arc::new(rate_limit::RateLimiter::new(
+            Arc::new(rate_limit::MemoryStore::default()),
+            config.rate_limit_default,
+            config.rate_limit_routes.clone(),
+        ));
+
+        let cache_store = (config.profile_cache_capacity > 0)
+            .then(|| Arc::new(cache::MemoryStore::new(config.profile_cache_capacity)) as Arc<dyn cache::CacheStore>);
+        let cache = Arc::new(cache::ProfileCache::new(cache_store, Duration::from_secs(config.profile_cache_ttl_secs)));
+
+        Ok(Self { replicas, pii, notifier, limiter, cache })
+    }
+}
+
+// The HTTP API with its middleware and the services its handlers extract.
+pub fn app(config: Arc<config::Config>, pool: PgPool, services: &Services) -> anyhow::Result<Router> {
+    let app = routes::router()
+                .layer(middleware::from_fn(replicas::read_your_writes))
+                .layer(middleware::from_fn(rate_limit::limit))
+                .layer(Extension(services.limiter.clone()))
+                .layer(Extension(services.pii.clone()))
+                .layer(Extension(services.notifier.clone()))
+                .layer(Extension(services.cache.clone()))
+                .layer(Extension(services.replicas.clone()))
+                .layer(Extension(config.clone()))
+                .layer(Extension(pool))
+                .layer(TraceLayer::new_for_http().make_span_with(logging::request_span))
+                .layer(PropagateRequestIdLayer::x_request_id())
+                .layer(SetRequestIdLayer::x_request_id(logging::MakeRequestHex));
+
+    layers::apply(app, &config)
+}
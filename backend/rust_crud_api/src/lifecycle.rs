@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::{
+    api_keys::ApiKey,
+    cache::ProfileCache,
+    errors::CustomError,
+    logging::query_span,
+    models::Status,
+    replicas::ReadPool,
+    tenants::Tenant,
+    views,
+};
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Transition {
+    pub id: i32,
+    pub employee_id: i32,
+    pub from_status: Status,
+    pub to_status: Status,
+    pub reason: String,
+    pub effective_date: NaiveDate,
+    pub recorded_by: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+    // The id of a record merged into this one that the change was made to.
+    pub merged_from: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct NewTransition {
+    pub to: Status,
+    pub reason: String,
+    // Defaults to today.
+    pub effective_date: Option<NaiveDate>,
+}
+
+// Moves an employee to a new status and records why. Transitions the
+// lifecycle does not allow are rejected with 409.
+#[tracing::instrument(name = "lifecycle.transition", skip_all, fields(employee_id = id))]
+pub async fn transition(Path(id): Path<i32>, tenant: Tenant, key: Option<ApiKey>, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>, Json(data): Json<NewTransition>) -> Result<(StatusCode, Json<Transition>), CustomError> {
+    if data.reason.trim().is_empty() {
+        return Err(CustomError::UnprocessableEntity);
+    }
+
+    let mut tx = tenant.begin(&pool).await?;
+    let id = views::resolve_id(&mut tx, &tenant, id).await?;
+    let sql = "SELECT status FROM employee WHERE id=$1 AND tenant_id=$2 AND erased_at IS NULL FOR UPDATE";
+    let current: Status = sqlx::query_scalar(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_one(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::TaskNotFound
+    })?;
+
+    if !current.can_become(data.to) {
+        return Err(CustomError::Conflict);
+    }
+
+    let sql = "UPDATE employee SET status=$1 WHERE id=$2 AND tenant_id=$3";
+    sqlx::query(sql)
+    .bind(data.to)
+    .bind(id)
+    .bind(tenant.id)
+    .execute(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    let sql = "INSERT INTO employee_status_transition (employee_id, tenant_id, from_status, to_status, reason, effective_date, recorded_by) values ($1, $2, $3, $4, $5, $6, $7) RETURNING id, employee_id, from_status, to_status, reason, effective_date, recorded_by, recorded_at, merged_from";
+    let transition = sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .bind(current)
+    .bind(data.to)
+    .bind(&data.reason)
+    .bind(data.effective_date.unwrap_or_else(|| Utc::now().date_naive()))
+    .bind(key.map(|key| key.name))
+    .fetch_one(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    cache.invalidate(tenant.id, id).await;
+
+    Ok((StatusCode::CREATED, Json(transition)))
+}
+
+// Past status changes of one employee, and of records merged into it, oldest
+// first.
+#[tracing::instrument(name = "lifecycle.history", skip_all, fields(employee_id = id))]
+pub async fn history(Path(id): Path<i32>, tenant: Tenant, ReadPool(pool, _): ReadPool) -> Result<Json<Vec<Transition>>, CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+    let id = views::resolve_id(&mut tx, &tenant, id).await?;
+    let transitions = transitions(&mut tx, &tenant, id).await?;
+
+    Ok(Json(transitions))
+}
+
+pub async fn transitions(conn: &mut sqlx::PgConnection, tenant: &Tenant, id: i32) -> Result<Vec<Transition>, CustomError> {
+    let sql = "SELECT id, employee_id, from_status, to_status, reason, effective_date, recorded_by, recorded_at, merged_from FROM employee_status_transition WHERE employee_id=$1 AND tenant_id=$2 ORDER BY id";
+    sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_all(conn)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })
+}
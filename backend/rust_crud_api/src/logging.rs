@@ -0,0 +1,129 @@
+use anyhow::Context;
+use axum::{
+    extract::MatchedPath,
+    http::{HeaderMap, HeaderValue, Request},
+};
+use opentelemetry::{global, propagation::Extractor, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+use rand::RngCore;
+use tower_http::request_id::{MakeRequestId, RequestId};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::Config;
+
+// Installs the global subscriber. `RUST_LOG` picks what gets logged and
+// `LOG_FORMAT=json` switches to one JSON object per line, with the enclosing
+// spans attached to every event. When `OTEL_EXPORTER_OTLP_ENDPOINT` is set,
+// spans are also exported over OTLP/gRPC.
+pub fn init(config: &Config) -> anyhow::Result<()> {
+    let filter = EnvFilter::try_new(&config.log_filter)
+        .map_err(|error| anyhow::anyhow!("Invalid RUST_LOG: {error}"))?;
+
+    let output = if config.log_json {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer().boxed()
+    };
+
+    let telemetry = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            global::set_text_map_propagator(TraceContextPropagator::new());
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+                .with_trace_config(trace::config().with_resource(Resource::new([KeyValue::new(
+                    "service.name",
+                    config.otel_service_name.clone(),
+                )])))
+                .install_batch(runtime::Tokio)
+                .context("Could not set up the OTLP exporter")?;
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output)
+        .with(telemetry)
+        .try_init()?;
+    Ok(())
+}
+
+// Flushes spans still waiting in the export batch.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+// Generates ids for requests that arrive without an `X-Request-Id`.
+#[derive(Clone, Copy, Default)]
+pub struct MakeRequestHex;
+
+impl MakeRequestId for MakeRequestHex {
+    fn make_request_id<B>(&mut self, _request: &Request<B>) -> Option<RequestId> {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        HeaderValue::from_str(&hex::encode(bytes)).ok().map(RequestId::new)
+    }
+}
+
+// Reads W3C trace context (`traceparent`, `tracestate`) from request headers.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+// The span every request runs in; database spans nest under it and so carry
+// the route and request id. A caller's `traceparent` makes it a child of the
+// caller's trace.
+pub fn request_span<B>(req: &Request<B>) -> Span {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| req.uri().path(), |matched| matched.as_str());
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+
+    let span = tracing::info_span!(
+        "request",
+        otel.name = %format!("{} {route}", req.method()),
+        otel.kind = "server",
+        method = %req.method(),
+        route,
+        request_id,
+    );
+    let parent = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(req.headers())));
+    span.set_parent(parent);
+    span
+}
+
+// A span around one database call, carrying the statement and, when it
+// touches a single employee, that employee's id.
+pub fn query_span(sql: &str, employee_id: Option<i32>) -> Span {
+    let operation = sql.split_whitespace().next().unwrap_or_default();
+    tracing::debug_span!(
+        "db.query",
+        otel.name = operation,
+        otel.kind = "client",
+        db.system = "postgresql",
+        db.statement = sql,
+        employee_id,
+    )
+}
@@ -1,53 +1,58 @@
-use axum::{
-    extract::Extension,
-    routing::{get, post, put, delete},
-    Router
-};
+use axum_server::tls_rustls::RustlsConfig;
+use clap::Parser;
 
 use sqlx::postgres::PgPoolOptions;
 use std::net::SocketAddr;
-use std::fs;
+use std::sync::Arc;
 use anyhow::Context;
-use tower_http::trace::TraceLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod errors;
-mod models;
-mod views;
+use rust_crud_api::{cli, config, grpc, jobs, logging, replicas, tls, Services};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let env = fs::read_to_string(".env").unwrap();
-    let (_, database_url) = env.split_once("=").unwrap();
+    let cli = cli::Cli::parse();
+    let config = Arc::new(config::Config::load()?);
 
-    tracing_subscriber::registry()
-    .with(tracing_subscriber::EnvFilter::new(
-        std::env::var("tower_http=trace")
-        .unwrap_or_else(|_| "example_tracing_aka_logging=debug, tower_http=debug".into()),
-    ))
-    .with(tracing_subscriber::fmt::layer())
-    .init();
+    match cli.command {
+        None | Some(cli::Command::Serve) => serve(config).await,
+        Some(command) => cli::run(command, cli.tenant, cli.output, config).await,
+    }
+}
+
+async fn serve(config: Arc<config::Config>) -> anyhow::Result<()> {
+    logging::init(&config)?;
 
     let pool = PgPoolOptions::new()
     .max_connections(50)
-    .connect(&database_url)
+    .connect(&config.database_url)
     .await
     .context("Could not connect to the database_url")?;
 
-    let app = Router::new()
-                .route("/profiles", get(views::all_profiles))
-                .route("/profile/:id", get(views::profile))
-                .route("/profile", post(views::post_profile))
-                .route("/profile/:id", put(views::update_profile))
-                .route("/profile/:id", delete(views::delete_profile))
-                .layer(Extension(pool))
-                .layer(TraceLayer::new_for_http());
+    let services = Services::new(&config, pool.clone())?;
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8000));
-    println!("Listening to {addr:?}");
+    replicas::watch(services.replicas.clone());
+    jobs::start(pool.clone(), config.clone()).context("Invalid job schedule")?;
+    grpc::start(config.clone(), pool.clone(), &services).context("Invalid gRPC settings")?;
 
-    axum::Server::bind(&addr).serve(app.into_make_service()).await.unwrap();
+    let app = rust_crud_api::app(config.clone(), pool, &services)?;
 
+    let addr = SocketAddr::from(([127, 0, 0, 1], 8000));
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    match tls::server_config(&config)? {
+        Some(server_config) => {
+            tracing::info!("Listening to {addr:?} with TLS");
+            let tls = RustlsConfig::from_config(server_config);
+            tls::watch(tls.clone(), config.clone());
+            axum_server::bind(addr).acceptor(tls::ClientCertAcceptor::new(tls)).serve(app).await?;
+        }
+        None => {
+            tracing::info!("Listening to {addr:?}");
+            axum::Server::bind(&addr).serve(app).await.unwrap();
+        }
+    }
+
+    logging::shutdown();
     Ok(())
 
 }
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{OriginalUri, Path, Query},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use tracing::Instrument;
+
+use crate::{
+    api_keys::Admin,
+    cache::ProfileCache,
+    errors::CustomError,
+    logging::query_span,
+    models::Profile,
+    negotiate::{Format, Negotiated},
+    pii::{Pii, PiiAccess},
+    replicas::ReadPool,
+    tenants::Tenant,
+    views,
+};
+
+const MAX_CANDIDATES: i64 = 500;
+
+#[derive(Deserialize)]
+pub struct CandidateQuery {
+    // Between 0 and 1. Defaults to 0.5.
+    pub min_score: Option<f64>,
+    // Defaults to 50.
+    pub limit: Option<i64>,
+}
+
+// A pair of employees that may be the same person. `id` is the older record.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Candidate {
+    pub id: i32,
+    pub ename: String,
+    pub duplicate_id: i32,
+    pub duplicate_ename: String,
+    pub score: f64,
+    pub name_similarity: f64,
+    pub same_email: bool,
+    pub same_phone: bool,
+}
+
+// A record folded into another one.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Merge {
+    pub merged_id: i32,
+    pub survivor_id: i32,
+    pub merged_by: Option<String>,
+    pub merged_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    // Folded into the employee in the path and then deleted.
+    pub duplicate_id: i32,
+}
+
+// Pairs with similar names (trigram similarity) or the same email address or
+// phone number, best first. The score weighs the name at 0.5, a shared email
+// at 0.4 and a shared phone number at 0.3, capped at 1.
+#[tracing::instrument(name = "merges.candidates", skip_all)]
+pub async fn candidates(Query(query): Query<CandidateQuery>, tenant: Tenant, ReadPool(pool, _): ReadPool) -> Result<Json<Vec<Candidate>>, CustomError> {
+    let min_score = query.min_score.unwrap_or(0.5);
+    let limit = query.limit.unwrap_or(50);
+    if !(0.0..=1.0).contains(&min_score) || !(1..=MAX_CANDIDATES).contains(&limit) {
+        return Err(CustomError::BadRequest);
+    }
+
+    let sql = "SELECT * FROM (\
+                   SELECT id, ename, duplicate_id, duplicate_ename, name_similarity, same_email, same_phone, \
+                   round(LEAST(1, 0.5 * name_similarity + 0.4 * same_email::int + 0.3 * same_phone::int)::numeric, 3)::float8 AS score \
+                   FROM (\
+                       SELECT a.id, a.ename, b.id AS duplicate_id, b.ename AS duplicate_ename, \
+                       round(similarity(a.ename, b.ename)::numeric, 3)::float8 AS name_similarity, \
+                       COALESCE(a.eemail_bidx = b.eemail_bidx, false) AS same_email, \
+                       COALESCE(a.econtact_bidx = b.econtact_bidx, false) AS same_phone \
+                       FROM employee a JOIN employee b ON b.tenant_id = a.tenant_id AND b.id > a.id \
+                       AND (a.ename % b.ename OR a.eemail_bidx = b.eemail_bidx OR a.econtact_bidx = b.econtact_bidx) \
+                       WHERE a.tenant_id=$1 AND a.erased_at IS NULL AND b.erased_at IS NULL\
+                   ) pairs\
+               ) scored WHERE score >= $2 ORDER BY score DESC, id, duplicate_id LIMIT $3";
+
+    let mut tx = tenant.begin(&pool).await?;
+    let candidates = sqlx::query_as(sql)
+    .bind(tenant.id)
+    .bind(min_score)
+    .bind(limit)
+    .fetch_all(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    Ok(Json(candidates))
+}
+
+// Folds the duplicate into the employee in the path. The employee keeps its
+// own values and gains the custom values it lacks and the earlier creation
+// time; a verification carries over when both records share the address or
+// number. The duplicate row is deleted, its versions and status changes move
+// to the employee marked with `merged_from`, its verification codes and stored
+// responses move along, and its id redirects to the employee from then on.
+#[tracing::instrument(name = "merges.merge", skip_all, fields(employee_id = id))]
+#[allow(clippy::too_many_arguments)]
+pub async fn merge(Admin(key): Admin, Path(id): Path<i32>, format: Format, tenant: Tenant, access: PiiAccess, Extension(pii): Extension<Arc<Pii>>, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>, Json(data): Json<MergeRequest>) -> Result<Negotiated<Profile>, CustomError> {
+    let duplicate_id = data.duplicate_id;
+    if duplicate_id == id {
+        return Err(CustomError::UnprocessableEntity);
+    }
+
+    let mut tx = tenant.begin(&pool).await?;
+
+    let sql = "SELECT id FROM employee WHERE id IN ($1, $2) AND tenant_id=$3 AND erased_at IS NULL ORDER BY id FOR UPDATE";
+    let locked: Vec<i32> = sqlx::query_scalar(sql)
+    .bind(id)
+    .bind(duplicate_id)
+    .bind(tenant.id)
+    .fetch_all(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    if locked.len() != 2 {
+        return Err(CustomError::TaskNotFound);
+    }
+
+    let sql = "UPDATE employee s SET custom = d.custom || s.custom, created_at = LEAST(s.created_at, d.created_at), \
+               eemail_verified_at = CASE WHEN s.eemail_bidx = d.eemail_bidx THEN GREATEST(s.eemail_verified_at, d.eemail_verified_at) ELSE s.eemail_verified_at END, \
+               econtact_verified_at = CASE WHEN s.econtact_bidx = d.econtact_bidx THEN GREATEST(s.econtact_verified_at, d.econtact_verified_at) ELSE s.econtact_verified_at END \
+               FROM employee d WHERE s.id=$1 AND s.tenant_id=$3 AND d.id=$2 AND d.tenant_id=$3";
+    let sql_delete = "DELETE FROM employee WHERE id=$2 AND tenant_id=$3";
+    // Earlier merges into the duplicate now point at the employee directly.
+    let sql_redirects = "UPDATE employee_merge SET survivor_id=$1 WHERE survivor_id=$2 AND tenant_id=$3";
+    // Runs after the delete, which closes the duplicate's current version.
+    let sql_versions = "UPDATE employee_history SET employee_id=$1, merged_from=COALESCE(merged_from, employee_id) WHERE employee_id=$2 AND tenant_id=$3";
+    let sql_transitions = "UPDATE employee_status_transition SET employee_id=$1, merged_from=COALESCE(merged_from, employee_id) WHERE employee_id=$2 AND tenant_id=$3";
+    // Codes sent to the duplicate's contacts confirm only while the employee
+    // holds the same value, and their attempts keep counting towards its limits.
+    let sql_verifications = "UPDATE contact_verification SET employee_id=$1 WHERE employee_id=$2 AND tenant_id=$3";
+    // So that erasing the employee also drops responses about the duplicate.
+    let sql_responses = "UPDATE idempotency_key SET employee_ids=array_replace(employee_ids, $2, $1) WHERE $2 = ANY(employee_ids) AND tenant_id=$3";
+    for sql in [sql, sql_delete, sql_redirects, sql_versions, sql_transitions, sql_verifications, sql_responses] {
+        sqlx::query(sql)
+        .bind(id)
+        .bind(duplicate_id)
+        .bind(tenant.id)
+        .execute(&mut tx)
+        .instrument(query_span(sql, Some(id)))
+        .await.map_err(|_| {
+            CustomError::InternalServerError
+        })?;
+    }
+
+    let sql = "INSERT INTO employee_merge (tenant_id, merged_id, survivor_id, merged_by) values ($1, $2, $3, $4) \
+               ON CONFLICT (tenant_id, merged_id) DO UPDATE SET survivor_id=EXCLUDED.survivor_id, merged_by=EXCLUDED.merged_by, merged_at=now()";
+    sqlx::query(sql)
+    .bind(tenant.id)
+    .bind(duplicate_id)
+    .bind(id)
+    .bind(&key.name)
+    .execute(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    let mut profile = views::find_profile(&mut tx, &tenant, id).await?;
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    cache.invalidate(tenant.id, id).await;
+    cache.invalidate(tenant.id, duplicate_id).await;
+
+    pii.reveal(&mut profile, &access)?;
+
+    Ok(Negotiated(format, profile))
+}
+
+// The employee a deleted id was merged into, if any.
+pub async fn survivor(conn: &mut PgConnection, tenant: &Tenant, id: i32) -> Result<Option<i32>, CustomError> {
+    let sql = "SELECT survivor_id FROM employee_merge WHERE merged_id=$1 AND tenant_id=$2";
+    sqlx::query_scalar(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_optional(conn)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })
+}
+
+// The records merged into the employee, or the one it was merged into.
+pub async fn merges(conn: &mut PgConnection, tenant: &Tenant, id: i32) -> Result<Vec<Merge>, CustomError> {
+    let sql = "SELECT merged_id, survivor_id, merged_by, merged_at FROM employee_merge WHERE (survivor_id=$1 OR merged_id=$1) AND tenant_id=$2 ORDER BY merged_at";
+    sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_all(conn)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })
+}
+
+// A permanent redirect to the same path with the survivor's id in place of
+// the merged one.
+pub fn redirect(OriginalUri(uri): &OriginalUri, survivor: i32) -> Response {
+    let path = uri.path().rsplit_once('/').map_or("", |(parent, _)| parent);
+    let location = match uri.query() {
+        Some(query) => format!("{path}/{survivor}?{query}"),
+        None => format!("{path}/{survivor}"),
+    };
+
+    Redirect::permanent(&location).into_response()
+}
@@ -1,6 +1,11 @@
 // pub mod model;
 
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
 
 #[derive(sqlx::FromRow, Deserialize, Serialize)]
 
@@ -10,6 +15,11 @@ pub struct Profile {
     pub ename: String,
     pub eemail: String,
     pub econtact: String,
+    pub status: Status,
+    pub custom: Json<Map<String, Value>>,
+    // Cleared whenever the address or number changes.
+    pub eemail_verified_at: Option<DateTime<Utc>>,
+    pub econtact_verified_at: Option<DateTime<Utc>>,
 }
 
 #[derive(sqlx::FromRow, Deserialize, Serialize)]
@@ -19,4 +29,125 @@ pub struct NewProfile {
     pub ename: String,
     pub eemail: String,
     pub econtact: String,
-}
\ No newline at end of file
+    // Values of the tenant's custom fields, keyed by field name. Left out (or
+    // an empty CSV cell), an update keeps the stored values; null clears them.
+    #[serde(default, deserialize_with = "custom_values", skip_serializing_if = "Option::is_none")]
+    pub custom: Option<Map<String, Value>>,
+}
+
+// Formats without nested values (CSV) carry custom fields as a JSON object in
+// a single string. XML carries every value as `{"$text": "..."}`; the text is
+// unwrapped here and the field types restored when the values are validated.
+fn custom_values<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Map<String, Value>>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Object(values) => Ok(Some(values
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::Object(mut element) if element.len() == 1 && element.contains_key("$text") => {
+                    (name, element.remove("$text").unwrap_or_default())
+                }
+                value => (name, value),
+            })
+            .collect())),
+        Value::Null => Ok(Some(Map::new())),
+        Value::String(text) if text.trim().is_empty() => Ok(None),
+        Value::String(text) => serde_json::from_str(&text).map(Some).map_err(serde::de::Error::custom),
+        _ => Err(serde::de::Error::custom("custom must be an object")),
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct ProfileFilter {
+    pub eemail: Option<String>,
+    pub econtact: Option<String>,
+    pub status: Option<Status>,
+    pub as_of: Option<DateTime<Utc>>,
+    // `id`, `eid`, `ename`, `status` or `custom.<field>`, with a leading `-`
+    // for descending order.
+    pub sort: Option<String>,
+    // `custom.<field>=<value>` parameters; other keys are ignored.
+    #[serde(flatten)]
+    pub params: HashMap<String, String>,
+}
+
+impl ProfileFilter {
+    // Exact-match filters on custom fields.
+    pub fn custom(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix("custom.")?, value.as_str())))
+    }
+}
+
+// Reads the record as it was at this time instead of as it is now.
+#[derive(Deserialize)]
+pub struct AsOf {
+    pub as_of: Option<DateTime<Utc>>,
+}
+
+// Where an employee is in their lifecycle. New profiles start onboarding.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, sqlx::Type, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum Status {
+    Onboarding,
+    Active,
+    OnLeave,
+    Terminated,
+}
+
+impl Status {
+    // Terminated employees can only come back through onboarding.
+    pub fn can_become(self, next: Status) -> bool {
+        use Status::*;
+        matches!(
+            (self, next),
+            (Onboarding, Active)
+                | (Onboarding, Terminated)
+                | (Active, OnLeave)
+                | (Active, Terminated)
+                | (OnLeave, Active)
+                | (OnLeave, Terminated)
+                | (Terminated, Onboarding)
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Onboarding => "onboarding",
+            Status::Active => "active",
+            Status::OnLeave => "on_leave",
+            Status::Terminated => "terminated",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Status; 4] = [Status::Onboarding, Status::Active, Status::OnLeave, Status::Terminated];
+
+    #[test]
+    fn follows_the_lifecycle() {
+        assert!(Status::Onboarding.can_become(Status::Active));
+        assert!(Status::Active.can_become(Status::OnLeave));
+        assert!(Status::OnLeave.can_become(Status::Active));
+        assert!(Status::Active.can_become(Status::Terminated));
+        assert!(!Status::Onboarding.can_become(Status::OnLeave));
+        assert!(!Status::OnLeave.can_become(Status::Onboarding));
+    }
+
+    #[test]
+    fn terminated_employees_only_come_back_through_onboarding() {
+        for next in ALL {
+            assert_eq!(Status::Terminated.can_become(next), next == Status::Onboarding);
+        }
+    }
+
+    #[test]
+    fn never_becomes_the_same_status() {
+        assert!(ALL.into_iter().all(|status| !status.can_become(status)));
+    }
+}
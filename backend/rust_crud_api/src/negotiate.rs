@@ -0,0 +1,346 @@
+use async_trait::async_trait;
+use axum::{
+    body::{Bytes, HttpBody},
+    extract::{FromRequest, FromRequestParts, Query},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        request::Parts,
+        HeaderMap, Request,
+    },
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    errors::CustomError,
+    models::{NewProfile, Profile, Status},
+    stats::{Counts, Point, Series},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+    Xml,
+    MessagePack,
+}
+
+impl Format {
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type.trim().to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Self::Json),
+            "text/csv" => Some(Self::Csv),
+            "application/xml" | "text/xml" => Some(Self::Xml),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "xml" => Some(Self::Xml),
+            "msgpack" | "messagepack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Xml => "application/xml",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+
+    // Picks the most preferred supported type from an `Accept` header.
+    fn from_accept(accept: &str) -> Option<Self> {
+        let mut ranges: Vec<(f32, &str)> = accept
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let media_type = params.next()?.trim();
+                let quality = params
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                Some((quality, media_type))
+            })
+            .filter(|(quality, _)| *quality > 0.0)
+            .collect();
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.into_iter().find_map(|(_, media_type)| Self::from_media_type(media_type))
+    }
+}
+
+#[derive(Deserialize)]
+struct FormatQuery {
+    format: Option<String>,
+}
+
+// The response format requested through `?format=` or the `Accept` header.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Format {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<FormatQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| CustomError::BadRequest)?;
+        if let Some(name) = query.format {
+            return Self::from_name(&name).ok_or(CustomError::NotAcceptable);
+        }
+
+        match parts.headers.get(ACCEPT).and_then(|v| v.to_str().ok()) {
+            Some(accept) if !accept.trim().is_empty() => Self::from_accept(accept).ok_or(CustomError::NotAcceptable),
+            _ => Ok(Self::Json),
+        }
+    }
+}
+
+// Resources that can be rendered in every supported format.
+pub trait Representation: Serialize {
+    fn to_csv(&self) -> Result<Vec<u8>, CustomError>;
+    fn to_xml(&self) -> Result<String, CustomError>;
+}
+
+fn csv_records<T: Serialize>(records: &[T]) -> Result<Vec<u8>, CustomError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        writer.serialize(record).map_err(|_| CustomError::InternalServerError)?;
+    }
+    writer.into_inner().map_err(|_| CustomError::InternalServerError)
+}
+
+// CSV has no nested values, so custom fields go in one column as JSON.
+#[derive(Serialize)]
+struct CsvProfile<'a> {
+    id: i32,
+    eid: &'a str,
+    ename: &'a str,
+    eemail: &'a str,
+    econtact: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<Status>,
+    custom: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    eemail_verified_at: Option<Option<DateTime<Utc>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    econtact_verified_at: Option<Option<DateTime<Utc>>>,
+}
+
+impl<'a> From<&'a Profile> for CsvProfile<'a> {
+    fn from(profile: &'a Profile) -> Self {
+        CsvProfile {
+            id: profile.id,
+            eid: &profile.eid,
+            ename: &profile.ename,
+            eemail: &profile.eemail,
+            econtact: &profile.econtact,
+            status: Some(profile.status),
+            custom: serde_json::to_string(&profile.custom).unwrap_or_default(),
+            eemail_verified_at: Some(profile.eemail_verified_at),
+            econtact_verified_at: Some(profile.econtact_verified_at),
+        }
+    }
+}
+
+impl<'a> From<&'a NewProfile> for CsvProfile<'a> {
+    fn from(profile: &'a NewProfile) -> Self {
+        CsvProfile {
+            id: profile.id,
+            eid: &profile.eid,
+            ename: &profile.ename,
+            eemail: &profile.eemail,
+            econtact: &profile.econtact,
+            status: None,
+            custom: profile.custom.as_ref().map(|custom| serde_json::to_string(custom).unwrap_or_default()).unwrap_or_default(),
+            eemail_verified_at: None,
+            econtact_verified_at: None,
+        }
+    }
+}
+
+// Wraps a list so each entry becomes a `<profile>` element.
+#[derive(Serialize)]
+struct XmlProfiles<'a> {
+    profile: &'a [Profile],
+}
+
+impl Representation for Profile {
+    fn to_csv(&self) -> Result<Vec<u8>, CustomError> {
+        csv_records(&[CsvProfile::from(self)])
+    }
+
+    fn to_xml(&self) -> Result<String, CustomError> {
+        quick_xml::se::to_string_with_root("profile", self).map_err(|_| CustomError::InternalServerError)
+    }
+}
+
+impl Representation for NewProfile {
+    fn to_csv(&self) -> Result<Vec<u8>, CustomError> {
+        csv_records(&[CsvProfile::from(self)])
+    }
+
+    fn to_xml(&self) -> Result<String, CustomError> {
+        quick_xml::se::to_string_with_root("profile", self).map_err(|_| CustomError::InternalServerError)
+    }
+}
+
+impl Representation for Vec<Profile> {
+    fn to_csv(&self) -> Result<Vec<u8>, CustomError> {
+        csv_records(&self.iter().map(CsvProfile::from).collect::<Vec<_>>())
+    }
+
+    fn to_xml(&self) -> Result<String, CustomError> {
+        quick_xml::se::to_string_with_root("profiles", &XmlProfiles { profile: self }).map_err(|_| CustomError::InternalServerError)
+    }
+}
+
+impl Representation for Counts {
+    // One column per dimension, then the count.
+    fn to_csv(&self) -> Result<Vec<u8>, CustomError> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let header = self.dimensions.iter().map(String::as_str).chain(["count"]);
+        writer.write_record(header).map_err(|_| CustomError::InternalServerError)?;
+        for group in &self.groups {
+            let cells = self.dimensions.iter().map(String::as_str).chain(["count"]).map(|column| match group.get(column) {
+                Some(serde_json::Value::String(text)) => text.clone(),
+                Some(serde_json::Value::Null) | None => String::new(),
+                Some(value) => value.to_string(),
+            });
+            writer.write_record(cells).map_err(|_| CustomError::InternalServerError)?;
+        }
+        writer.into_inner().map_err(|_| CustomError::InternalServerError)
+    }
+
+    fn to_xml(&self) -> Result<String, CustomError> {
+        quick_xml::se::to_string_with_root("stats", self).map_err(|_| CustomError::InternalServerError)
+    }
+}
+
+// Wraps the points so each becomes a `<point>` element.
+#[derive(Serialize)]
+struct XmlSeries<'a> {
+    interval: crate::stats::Interval,
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+    point: &'a [Point],
+}
+
+impl Representation for Series {
+    fn to_csv(&self) -> Result<Vec<u8>, CustomError> {
+        csv_records(&self.points)
+    }
+
+    fn to_xml(&self) -> Result<String, CustomError> {
+        let series = XmlSeries { interval: self.interval, from: self.from, to: self.to, point: &self.points };
+        quick_xml::se::to_string_with_root("timeseries", &series).map_err(|_| CustomError::InternalServerError)
+    }
+}
+
+// A response rendered in the negotiated format.
+pub struct Negotiated<T>(pub Format, pub T);
+
+impl<T: Representation> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let Negotiated(format, value) = self;
+        let body = match format {
+            Format::Json => serde_json::to_vec(&value).map_err(|_| CustomError::InternalServerError),
+            Format::Csv => value.to_csv(),
+            Format::Xml => value.to_xml().map(String::into_bytes),
+            Format::MessagePack => rmp_serde::to_vec_named(&value).map_err(|_| CustomError::InternalServerError),
+        };
+
+        match body {
+            Ok(body) => ([(CONTENT_TYPE, format.content_type())], body).into_response(),
+            Err(error) => error.into_response(),
+        }
+    }
+}
+
+// A request body parsed according to its `Content-Type`.
+pub struct Payload<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for Payload<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = CustomError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let format = content_format(req.headers())?;
+        let bytes = Bytes::from_request(req, state).await.map_err(|_| CustomError::BadRequest)?;
+
+        let value = match format {
+            Format::Json => serde_json::from_slice(&bytes).map_err(|_| CustomError::UnprocessableEntity)?,
+            Format::Csv => csv::Reader::from_reader(bytes.as_ref())
+                .deserialize()
+                .next()
+                .ok_or(CustomError::UnprocessableEntity)?
+                .map_err(|_| CustomError::UnprocessableEntity)?,
+            Format::Xml => {
+                let text = std::str::from_utf8(&bytes).map_err(|_| CustomError::UnprocessableEntity)?;
+                quick_xml::de::from_str(text).map_err(|_| CustomError::UnprocessableEntity)?
+            }
+            Format::MessagePack => rmp_serde::from_slice(&bytes).map_err(|_| CustomError::UnprocessableEntity)?,
+        };
+
+        Ok(Payload(value))
+    }
+}
+
+fn content_format(headers: &HeaderMap) -> Result<Format, CustomError> {
+    let Some(content_type) = headers.get(CONTENT_TYPE) else {
+        return Err(CustomError::UnsupportedMediaType);
+    };
+    let media_type = content_type
+        .to_str()
+        .map_err(|_| CustomError::UnsupportedMediaType)?
+        .split(';')
+        .next()
+        .unwrap_or_default();
+
+    match Format::from_media_type(media_type) {
+        Some(format) if !media_type.contains('*') => Ok(format),
+        _ => Err(CustomError::UnsupportedMediaType),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_the_highest_quality() {
+        assert_eq!(Format::from_accept("application/json;q=0.5, text/csv"), Some(Format::Csv));
+        assert_eq!(Format::from_accept("text/csv; q=0.2, application/xml; q=0.9"), Some(Format::Xml));
+    }
+
+    #[test]
+    fn keeps_header_order_for_equal_quality() {
+        assert_eq!(Format::from_accept("application/msgpack, application/json"), Some(Format::MessagePack));
+    }
+
+    #[test]
+    fn skips_unsupported_and_refused_types() {
+        assert_eq!(Format::from_accept("text/html, text/csv;q=0.1"), Some(Format::Csv));
+        assert_eq!(Format::from_accept("application/xml;q=0, text/csv;q=0.1"), Some(Format::Csv));
+        assert_eq!(Format::from_accept("text/html, image/png"), None);
+    }
+
+    #[test]
+    fn treats_wildcards_as_json() {
+        assert_eq!(Format::from_accept("*/*"), Some(Format::Json));
+        assert_eq!(Format::from_accept("text/html, */*;q=0.8"), Some(Format::Json));
+    }
+}
@@ -0,0 +1,164 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Serialize;
+use tokio::{
+    fs::OpenOptions,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::Mutex,
+};
+
+use crate::config::Config;
+
+#[derive(Clone, Copy, PartialEq, Serialize, serde::Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Channel {
+    Email,
+    Phone,
+}
+
+#[derive(Serialize)]
+pub struct Message {
+    pub channel: Channel,
+    // An email address or a phone number.
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Delivers messages to employees. Implementations for real email or SMS
+// providers can be plugged in next to the development ones below.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, message: &Message) -> anyhow::Result<()>;
+}
+
+pub fn from_config(config: &Config) -> anyhow::Result<Arc<dyn Notifier>> {
+    let Some(notifier) = &config.notifier else {
+        return Ok(Arc::new(Disabled));
+    };
+
+    if let Some(path) = notifier.strip_prefix("file:") {
+        return Ok(Arc::new(FileSink { path: PathBuf::from(path), lock: Mutex::new(()) }));
+    }
+    if let Some(address) = notifier.strip_prefix("smtp://") {
+        let address = address.trim_end_matches('/');
+        let address = match address.contains(':') {
+            true => address.to_string(),
+            false => format!("{address}:25"),
+        };
+        return Ok(Arc::new(Smtp {
+            address,
+            from: config.notifier_from.clone(),
+            sms_gateway_domain: config.sms_gateway_domain.clone(),
+        }));
+    }
+
+    bail!("expected file:<path> or smtp://host:port")
+}
+
+struct Disabled;
+
+#[async_trait]
+impl Notifier for Disabled {
+    async fn send(&self, _: &Message) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("NOTIFIER is not set"))
+    }
+}
+
+// Appends each message as a JSON line, for development and tests.
+struct FileSink {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+#[async_trait]
+impl Notifier for FileSink {
+    async fn send(&self, message: &Message) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(&serde_json::json!({"sent_at": Utc::now(), "message": message}))?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().await;
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path).await?;
+        file.write_all(&line).await?;
+        Ok(())
+    }
+}
+
+// Plain SMTP without authentication or TLS, meant for a local stand-in such
+// as MailHog or Mailpit. Phone messages go to `<digits>@<SMS_GATEWAY_DOMAIN>`.
+struct Smtp {
+    address: String,
+    from: String,
+    sms_gateway_domain: Option<String>,
+}
+
+#[async_trait]
+impl Notifier for Smtp {
+    async fn send(&self, message: &Message) -> anyhow::Result<()> {
+        let to = match message.channel {
+            Channel::Email => message.to.clone(),
+            Channel::Phone => {
+                let domain = self.sms_gateway_domain.as_deref().context("SMS_GATEWAY_DOMAIN is not set")?;
+                let digits: String = message.to.chars().filter(char::is_ascii_digit).collect();
+                format!("{digits}@{domain}")
+            }
+        };
+        if [&self.from, &to].iter().any(|address| address.contains(['\r', '\n', '<', '>'])) {
+            bail!("invalid address");
+        }
+
+        let stream = TcpStream::connect(&self.address).await.with_context(|| format!("could not connect to {}", self.address))?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        reply(&mut reader, 220).await?;
+        for (command, expected) in [
+            ("EHLO localhost".to_string(), 250),
+            (format!("MAIL FROM:<{}>", self.from), 250),
+            (format!("RCPT TO:<{to}>"), 250),
+            ("DATA".to_string(), 354),
+        ] {
+            writer.write_all(format!("{command}\r\n").as_bytes()).await?;
+            reply(&mut reader, expected).await?;
+        }
+
+        let subject = message.subject.replace(['\r', '\n'], " ");
+        let mut data = format!("From: <{}>\r\nTo: <{to}>\r\nSubject: {subject}\r\nDate: {}\r\n\r\n", self.from, Utc::now().to_rfc2822());
+        for line in message.body.lines() {
+            // Dot-stuffing, so a line of "." cannot end the message early.
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".\r\n");
+        writer.write_all(data.as_bytes()).await?;
+        reply(&mut reader, 250).await?;
+
+        writer.write_all(b"QUIT\r\n").await?;
+        Ok(())
+    }
+}
+
+// Reads a possibly multi-line reply and checks its code.
+async fn reply<R: AsyncBufReadExt + Unpin>(reader: &mut R, expected: u16) -> anyhow::Result<()> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            bail!("SMTP server closed the connection");
+        }
+        let code: u16 = line.get(..3).and_then(|code| code.parse().ok()).context("malformed SMTP reply")?;
+        if code != expected {
+            bail!("SMTP server replied {}", line.trim_end());
+        }
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
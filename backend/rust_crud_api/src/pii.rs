@@ -0,0 +1,388 @@
+use std::{collections::HashMap, sync::Arc};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use axum::{extract::FromRequestParts, http::request::Parts, Extension, Json};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::{
+    api_keys::{ApiKey, GlobalAdmin, ADMIN_SCOPE},
+    cache::ProfileCache,
+    config::Config,
+    errors::CustomError,
+    logging::query_span,
+    models::Profile,
+};
+
+pub const PII_READ_SCOPE: &str = "pii:read";
+
+const PREFIX: &str = "enc";
+const NONCE_LEN: usize = 12;
+
+// Application-level encryption of the contact columns. Values are stored as
+// `enc:<key id>:<base64 nonce+ciphertext>` so older keys can still decrypt
+// rows written before a rotation.
+pub struct Pii {
+    keys: HashMap<String, Aes256Gcm>,
+    active: String,
+    index_key: Vec<u8>,
+}
+
+impl Pii {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let mut keys = HashMap::new();
+        for entry in config.pii_keys.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (id, key) = entry
+                .trim()
+                .split_once(':')
+                .context("PII_KEYS entries must look like <id>:<base64 key>")?;
+            let key = STANDARD.decode(key.trim()).context("PII_KEYS key is not valid base64")?;
+            let cipher = Aes256Gcm::new_from_slice(&key)
+                .map_err(|_| anyhow::anyhow!("PII_KEYS key {id:?} must be 32 bytes"))?;
+            keys.insert(id.trim().to_string(), cipher);
+        }
+
+        let active = config.pii_active_key.clone();
+        anyhow::ensure!(keys.contains_key(&active), "PII_ACTIVE_KEY {active:?} is not in PII_KEYS");
+
+        let index_key = STANDARD
+            .decode(&config.pii_index_key)
+            .context("PII_INDEX_KEY is not valid base64")?;
+        anyhow::ensure!(index_key.len() >= 32, "PII_INDEX_KEY must be at least 32 bytes");
+
+        Ok(Self { keys, active, index_key })
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        self.seal(plaintext.as_bytes())
+    }
+
+    // Values without the `enc:` prefix predate encryption and are returned
+    // as they are until re-encrypted.
+    pub fn decrypt(&self, value: &str) -> Result<String, CustomError> {
+        String::from_utf8(self.open(value)?).map_err(|_| CustomError::InternalServerError)
+    }
+
+    // Encrypts any bytes, e.g. a stored response, as the contact columns are.
+    pub fn seal(&self, plaintext: &[u8]) -> String {
+        let cipher = &self.keys[&self.active];
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut sealed = nonce.to_vec();
+        sealed.extend(cipher.encrypt(&nonce, plaintext).expect("AES-GCM encryption failed"));
+        format!("{PREFIX}:{}:{}", self.active, STANDARD.encode(sealed))
+    }
+
+    pub fn open(&self, value: &str) -> Result<Vec<u8>, CustomError> {
+        let Some(rest) = value.strip_prefix(PREFIX).and_then(|rest| rest.strip_prefix(':')) else {
+            return Ok(value.as_bytes().to_vec());
+        };
+
+        let (id, sealed) = rest.split_once(':').ok_or(CustomError::InternalServerError)?;
+        let cipher = self.keys.get(id).ok_or(CustomError::InternalServerError)?;
+        let sealed = STANDARD.decode(sealed).map_err(|_| CustomError::InternalServerError)?;
+        if sealed.len() < NONCE_LEN {
+            return Err(CustomError::InternalServerError);
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CustomError::InternalServerError)
+    }
+
+    fn is_current(&self, value: &str) -> bool {
+        value.starts_with(&format!("{PREFIX}:{}:", self.active))
+    }
+
+    pub fn email_index(&self, email: &str) -> String {
+        self.blind_index(&email.trim().to_lowercase())
+    }
+
+    pub fn contact_index(&self, contact: &str) -> String {
+        let digits: String = contact.chars().filter(char::is_ascii_digit).collect();
+        self.blind_index(&digits)
+    }
+
+    // Verification codes are stored keyed like the blind indexes, so a leaked
+    // table does not reveal outstanding codes.
+    pub fn code_index(&self, code: &str) -> String {
+        self.blind_index(&format!("verification-code:{code}"))
+    }
+
+    fn blind_index(&self, normalised: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.index_key).expect("HMAC accepts any key length");
+        mac.update(normalised.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    // Decrypts the contact fields of a stored profile, masking them unless the
+    // caller may read personal data.
+    pub fn reveal(&self, profile: &mut Profile, access: &PiiAccess) -> Result<(), CustomError> {
+        let email = self.decrypt(&profile.eemail)?;
+        let contact = self.decrypt(&profile.econtact)?;
+
+        if access.0 {
+            profile.eemail = email;
+            profile.econtact = contact;
+        } else {
+            profile.eemail = mask_email(&email);
+            profile.econtact = mask_contact(&contact);
+        }
+
+        Ok(())
+    }
+}
+
+// `jane@example.com` -> `j***@example.com`
+pub fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            let first = local.chars().next().map(String::from).unwrap_or_default();
+            format!("{first}***@{domain}")
+        }
+        None => "***".to_string(),
+    }
+}
+
+// `Jane@Example.com` -> `example.com`; the part of an address masking leaves
+// visible, kept in plain text for reports.
+pub fn email_domain(email: &str) -> Option<String> {
+    email
+        .trim()
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.to_lowercase())
+        .filter(|domain| !domain.is_empty())
+}
+
+// `+1 555 0100` -> `** *** 0100`
+pub fn mask_contact(contact: &str) -> String {
+    let chars: Vec<char> = contact.chars().collect();
+    let visible = chars.len().saturating_sub(4);
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| if i < visible && !c.is_whitespace() { '*' } else { *c })
+        .collect()
+}
+
+// Whether the caller may see unmasked contact data: their API key must carry
+// the `pii:read` (or admin) scope.
+pub struct PiiAccess(pub bool);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for PiiAccess {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if !parts.headers.contains_key("X-Api-Key") {
+            return Ok(PiiAccess(false));
+        }
+
+        let key = ApiKey::from_request_parts(parts, state).await?;
+        Ok(PiiAccess(key.scopes.iter().any(|scope| scope == PII_READ_SCOPE || scope == ADMIN_SCOPE)))
+    }
+}
+
+// Rows re-encrypted per statement.
+const REENCRYPT_BATCH: usize = 500;
+
+// Re-encrypts contact data still stored in plain text or under a retired key,
+// and fills in missing blind indexes and email domains. Runs across all tenants
+// in one transaction with the versioning and change-notification triggers off,
+// since a new ciphertext is not a change to the employee; the table is locked
+// until it commits.
+pub async fn reencrypt(_: GlobalAdmin, Extension(pii): Extension<Arc<Pii>>, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>) -> Result<Json<Value>, CustomError> {
+    let mut tx = pool.begin().await.map_err(|_| CustomError::InternalServerError)?;
+
+    let sql = "ALTER TABLE employee DISABLE TRIGGER employee_versioning, DISABLE TRIGGER employee_changes";
+    sqlx::query(sql)
+    .execute(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    let sql = "SELECT tenant_id, id, eemail, econtact, eemail_domain IS NULL AND erased_at IS NULL FROM employee ORDER BY tenant_id, id";
+    let rows: Vec<(i32, i32, String, String, bool)> = sqlx::query_as(sql)
+    .fetch_all(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    let mut stale = Vec::new();
+    for (tenant_id, id, eemail, econtact, missing_domain) in rows {
+        if !(pii.is_current(&eemail) && pii.is_current(&econtact) && !missing_domain) {
+            stale.push((tenant_id, id, pii.decrypt(&eemail)?, pii.decrypt(&econtact)?));
+        }
+    }
+
+    for batch in stale.chunks(REENCRYPT_BATCH) {
+        let sql = "UPDATE employee e SET eemail=u.eemail, econtact=u.econtact, eemail_bidx=u.eemail_bidx, econtact_bidx=u.econtact_bidx, eemail_domain=u.eemail_domain \
+                   FROM unnest($1::integer[], $2::integer[], $3::text[], $4::text[], $5::text[], $6::text[], $7::text[]) \
+                   AS u(tenant_id, id, eemail, econtact, eemail_bidx, econtact_bidx, eemail_domain) \
+                   WHERE e.tenant_id=u.tenant_id AND e.id=u.id";
+        sqlx::query(sql)
+        .bind(batch.iter().map(|row| row.0).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| row.1).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| pii.encrypt(&row.2)).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| pii.encrypt(&row.3)).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| pii.email_index(&row.2)).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| pii.contact_index(&row.3)).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| email_domain(&row.2)).collect::<Vec<_>>())
+        .execute(&mut tx)
+        .instrument(query_span(sql, None))
+        .await.map_err(|_| {
+            CustomError::InternalServerError
+        })?;
+    }
+
+    let sql = "ALTER TABLE employee ENABLE TRIGGER employee_versioning, ENABLE TRIGGER employee_changes";
+    sqlx::query(sql)
+    .execute(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    // Past versions are kept readable under the current key too.
+    let sql = "SELECT history_id, data->>'eemail', data->>'econtact' FROM employee_history ORDER BY history_id";
+    let versions: Vec<(i64, String, String)> = sqlx::query_as(sql)
+    .fetch_all(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    let mut stale_versions = Vec::new();
+    for (history_id, eemail, econtact) in versions {
+        if !(pii.is_current(&eemail) && pii.is_current(&econtact)) {
+            stale_versions.push((history_id, pii.decrypt(&eemail)?, pii.decrypt(&econtact)?));
+        }
+    }
+
+    for batch in stale_versions.chunks(REENCRYPT_BATCH) {
+        let sql = "UPDATE employee_history h SET data = h.data || jsonb_build_object('eemail', u.eemail, 'econtact', u.econtact) \
+                   FROM unnest($1::bigint[], $2::text[], $3::text[]) AS u(history_id, eemail, econtact) \
+                   WHERE h.history_id=u.history_id";
+        sqlx::query(sql)
+        .bind(batch.iter().map(|row| row.0).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| pii.encrypt(&row.1)).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| pii.encrypt(&row.2)).collect::<Vec<_>>())
+        .execute(&mut tx)
+        .instrument(query_span(sql, None))
+        .await.map_err(|_| {
+            CustomError::InternalServerError
+        })?;
+    }
+
+    // Stored idempotent responses expire, but may outlive the retired key.
+    let sql = "SELECT tenant_id, key, body FROM idempotency_key WHERE body IS NOT NULL AND body NOT LIKE $1";
+    let responses: Vec<(i32, String, String)> = sqlx::query_as(sql)
+    .bind(format!("{PREFIX}:{}:%", pii.active))
+    .fetch_all(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    for batch in responses.chunks(REENCRYPT_BATCH) {
+        let sql = "UPDATE idempotency_key k SET body=u.body FROM unnest($1::integer[], $2::text[], $3::text[]) AS u(tenant_id, key, body) \
+                   WHERE k.tenant_id=u.tenant_id AND k.key=u.key";
+        let bodies = batch.iter().map(|row| pii.open(&row.2).map(|body| pii.seal(&body))).collect::<Result<Vec<_>, _>>()?;
+        sqlx::query(sql)
+        .bind(batch.iter().map(|row| row.0).collect::<Vec<_>>())
+        .bind(batch.iter().map(|row| row.1.clone()).collect::<Vec<_>>())
+        .bind(bodies)
+        .execute(&mut tx)
+        .instrument(query_span(sql, None))
+        .await.map_err(|_| {
+            CustomError::InternalServerError
+        })?;
+    }
+
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+
+    // Cached rows still hold the old ciphertext.
+    if !stale.is_empty() {
+        cache.clear().await;
+    }
+
+    Ok(Json(json!({"updated": stale.len(), "history_updated": stale_versions.len(), "responses_updated": responses.len()})))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::Json;
+
+    use super::*;
+    use crate::models::Status;
+
+    fn pii() -> Pii {
+        let keys = HashMap::from([("k1".to_string(), Aes256Gcm::new_from_slice(&[7; 32]).unwrap())]);
+        Pii { keys, active: "k1".to_string(), index_key: vec![9; 32] }
+    }
+
+    fn profile(pii: &Pii) -> Profile {
+        Profile {
+            id: 1,
+            eid: "E1".to_string(),
+            ename: "Jane".to_string(),
+            eemail: pii.encrypt("jane@example.com"),
+            econtact: pii.encrypt("+1 555 0100"),
+            status: Status::Active,
+            custom: Json(Default::default()),
+            eemail_verified_at: None,
+            econtact_verified_at: None,
+        }
+    }
+
+    #[test]
+    fn masks_emails_down_to_the_first_letter_and_domain() {
+        assert_eq!(mask_email("jane@example.com"), "j***@example.com");
+        assert_eq!(mask_email("@example.com"), "***@example.com");
+        assert_eq!(mask_email("not an address"), "***");
+    }
+
+    #[test]
+    fn masks_contacts_but_the_last_four_characters() {
+        assert_eq!(mask_contact("+1 555 0100"), "** *** 0100");
+        assert_eq!(mask_contact("0100"), "0100");
+        assert_eq!(mask_contact(""), "");
+    }
+
+    #[test]
+    fn reveals_contacts_only_with_access() {
+        let pii = pii();
+
+        let mut masked = profile(&pii);
+        pii.reveal(&mut masked, &PiiAccess(false)).unwrap();
+        assert_eq!((masked.eemail.as_str(), masked.econtact.as_str()), ("j***@example.com", "** *** 0100"));
+
+        let mut revealed = profile(&pii);
+        pii.reveal(&mut revealed, &PiiAccess(true)).unwrap();
+        assert_eq!((revealed.eemail.as_str(), revealed.econtact.as_str()), ("jane@example.com", "+1 555 0100"));
+    }
+
+    #[test]
+    fn seals_bytes_under_the_active_key() {
+        let pii = pii();
+        let sealed = pii.seal(&[0, 159, 255]);
+        assert!(pii.is_current(&sealed));
+        assert_eq!(pii.open(&sealed).unwrap(), vec![0, 159, 255]);
+    }
+
+    #[test]
+    fn passes_plain_text_from_before_encryption_through() {
+        assert_eq!(pii().decrypt("jane@example.com").unwrap(), "jane@example.com");
+    }
+}
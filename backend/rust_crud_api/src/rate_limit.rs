@@ -0,0 +1,331 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, MatchedPath},
+    http::{request::Parts, HeaderValue, Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+use crate::{
+    api_keys::ApiKey,
+    errors::CustomError,
+    tls::ClientIdentity,
+    tokens::{bearer, Claims},
+};
+
+// Buckets untouched for this long are dropped first when the store is full.
+const IDLE_EXPIRY: Duration = Duration::from_secs(3600);
+
+// The most buckets the in-memory store holds by default; idle buckets are
+// only dropped to make room.
+const MAX_BUCKETS: usize = 100_000;
+
+#[derive(Clone, Copy)]
+pub struct Limit {
+    pub capacity: u32,
+    pub period_secs: u64,
+}
+
+impl FromStr for Limit {
+    type Err = anyhow::Error;
+
+    // Parses `<requests>/<seconds>`, e.g. `100/60`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (capacity, period) = s
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("expected <requests>/<seconds>, got {s:?}"))?;
+        let limit = Limit {
+            capacity: capacity.trim().parse()?,
+            period_secs: period.trim().parse()?,
+        };
+        anyhow::ensure!(limit.capacity > 0 && limit.period_secs > 0, "limit must be non-zero");
+        Ok(limit)
+    }
+}
+
+#[derive(Clone)]
+pub struct RouteLimit {
+    pub method: Option<Method>,
+    pub path: String,
+    pub limit: Limit,
+}
+
+impl FromStr for RouteLimit {
+    type Err = anyhow::Error;
+
+    // Parses `[METHOD ]<path>=<requests>/<seconds>`, e.g. `GET /v1/profiles=20/60`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (route, limit) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expected <route>=<limit>, got {s:?}"))?;
+        let (method, path) = match route.trim().split_once(' ') {
+            Some((method, path)) => (Some(method.trim().parse()?), path.trim()),
+            None => (None, route.trim()),
+        };
+        Ok(RouteLimit {
+            method,
+            path: path.to_string(),
+            limit: limit.parse()?,
+        })
+    }
+}
+
+pub struct Decision {
+    pub allowed: bool,
+    pub remaining: u32,
+    pub reset_after: Duration,
+}
+
+// Backing storage for token buckets. The in-memory store only limits a single
+// instance; a shared implementation (e.g. Redis) can be plugged in instead.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    async fn acquire(&self, key: &str, limit: Limit) -> Decision;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: Limit, now: Instant) -> Self {
+        Self { tokens: f64::from(limit.capacity), updated: now }
+    }
+
+    // Refills the tokens earned since the last request, at `capacity` per
+    // period, and takes one if there is one.
+    fn take(&mut self, limit: Limit, now: Instant) -> Decision {
+        let capacity = f64::from(limit.capacity);
+        let rate = capacity / limit.period_secs as f64;
+
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated = now;
+
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+
+        let reset_after = if allowed {
+            (capacity - self.tokens) / rate
+        } else {
+            (1.0 - self.tokens) / rate
+        };
+
+        Decision {
+            allowed,
+            remaining: self.tokens.floor() as u32,
+            reset_after: Duration::from_secs_f64(reset_after),
+        }
+    }
+}
+
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    max_buckets: usize,
+}
+
+impl MemoryStore {
+    pub fn new(max_buckets: usize) -> Self {
+        Self { buckets: Mutex::default(), max_buckets: max_buckets.max(2) }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(MAX_BUCKETS)
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn acquire(&self, key: &str, limit: Limit) -> Decision {
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= self.max_buckets && !buckets.contains_key(key) {
+            // Drops idle buckets and the least recently used half at once, so
+            // a flood of new clients does not pay for a scan on every request.
+            // An evicted client starts again with a full bucket.
+            let mut updated: Vec<Instant> = buckets.values().map(|bucket| bucket.updated).collect();
+            let middle = updated.len() / 2;
+            let cutoff = *updated.select_nth_unstable(middle).1;
+            buckets.retain(|_, bucket| bucket.updated > cutoff && now.duration_since(bucket.updated) < IDLE_EXPIRY);
+        }
+
+        buckets
+            .entry(key.to_string())
+            .or_insert_with(|| Bucket::full(limit, now))
+            .take(limit, now)
+    }
+}
+
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    default: Limit,
+    routes: Vec<RouteLimit>,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>, default: Limit, routes: Vec<RouteLimit>) -> Self {
+        Self { store, default, routes }
+    }
+
+    fn limit_for(&self, method: &Method, path: &str) -> Limit {
+        self.routes
+            .iter()
+            .find(|route| route.path == path && route.method.as_ref().is_none_or(|m| m == method))
+            .map_or(self.default, |route| route.limit)
+    }
+
+    // Takes a request for the route from the caller's bucket, returning the
+    // route's limit and the outcome. The gRPC server shares it with the
+    // middleware below.
+    pub async fn acquire(&self, parts: &mut Parts, path: &str) -> (Limit, Decision) {
+        let limit = self.limit_for(&parts.method, path);
+        let client = client_key(parts).await;
+        let key = format!("{client}|{} {path}", parts.method);
+        (limit, self.store.acquire(&key, limit).await)
+    }
+}
+
+pub async fn limit<B>(
+    Extension(limiter): Extension<Arc<RateLimiter>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| req.uri().path().to_string(), |matched| matched.as_str().to_string());
+
+    let (mut parts, body) = req.into_parts();
+    let (limit, decision) = limiter.acquire(&mut parts, &path).await;
+    let req = Request::from_parts(parts, body);
+
+    let mut response = if decision.allowed {
+        next.run(req).await
+    } else {
+        let mut response = CustomError::TooManyRequests.into_response();
+        let retry_after = decision.reset_after.as_secs().max(1);
+        response.headers_mut().insert("Retry-After", HeaderValue::from(retry_after));
+        response
+    };
+
+    let headers = response.headers_mut();
+    headers.insert("RateLimit-Limit", HeaderValue::from(limit.capacity));
+    headers.insert("RateLimit-Remaining", HeaderValue::from(decision.remaining));
+    headers.insert("RateLimit-Reset", HeaderValue::from(decision.reset_after.as_secs()));
+
+    response
+}
+
+// Identifies the caller by API key, then bearer token subject, then client
+// certificate, then peer address. Keys and tokens only count once they are
+// verified; anything else shares the bucket of the address it came from. The
+// extractors keep what they resolve in the request extensions, so handlers
+// reuse it instead of looking the key up again.
+async fn client_key(parts: &mut Parts) -> String {
+    if parts.headers.contains_key("X-Api-Key") {
+        if let Ok(key) = ApiKey::from_request_parts(parts, &()).await {
+            return format!("key:{}", key.id);
+        }
+    } else if bearer(&parts.headers).is_some() {
+        if let Ok(claims) = Claims::from_request_parts(parts, &()).await {
+            return format!("sub:{}", claims.sub);
+        }
+    }
+
+    if let Some(Some(identity)) = parts.extensions.get::<Option<ClientIdentity>>() {
+        return format!("cert:{}", identity.fingerprint);
+    }
+
+    match parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: Limit = Limit { capacity: 10, period_secs: 60 };
+
+    #[test]
+    fn parses_limits() {
+        let limit: Limit = "100/60".parse().unwrap();
+        assert_eq!((limit.capacity, limit.period_secs), (100, 60));
+        assert!("0/60".parse::<Limit>().is_err());
+        assert!("100".parse::<Limit>().is_err());
+
+        let route: RouteLimit = "GET /v1/profiles=20/60".parse().unwrap();
+        assert_eq!(route.method, Some(Method::GET));
+        assert_eq!(route.path, "/v1/profiles");
+        assert_eq!(route.limit.capacity, 20);
+    }
+
+    #[test]
+    fn allows_the_capacity_then_refuses() {
+        let now = Instant::now();
+        let mut bucket = Bucket::full(LIMIT, now);
+
+        for remaining in (0..10).rev() {
+            let decision = bucket.take(LIMIT, now);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+
+        let decision = bucket.take(LIMIT, now);
+        assert!(!decision.allowed);
+        // One token comes back every 6 seconds.
+        assert_eq!(decision.reset_after, Duration::from_secs(6));
+    }
+
+    #[test]
+    fn refills_at_capacity_per_period() {
+        let now = Instant::now();
+        let mut bucket = Bucket::full(LIMIT, now);
+        for _ in 0..10 {
+            bucket.take(LIMIT, now);
+        }
+
+        let decision = bucket.take(LIMIT, now + Duration::from_secs(12));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+
+        // Never more than the capacity, however long the client was away.
+        let decision = bucket.take(LIMIT, now + Duration::from_secs(3600));
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 9);
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used_buckets_when_full() {
+        let store = MemoryStore::new(4);
+        for key in ["a", "b", "c", "d"] {
+            store.acquire(key, LIMIT).await;
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        store.acquire("a", LIMIT).await;
+
+        store.acquire("e", LIMIT).await;
+
+        let buckets = store.buckets.lock().unwrap();
+        assert!(buckets.len() <= 4);
+        assert!(buckets.contains_key("a") && buckets.contains_key("e"));
+        assert!(!buckets.contains_key("b"));
+    }
+}
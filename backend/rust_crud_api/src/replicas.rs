@@ -0,0 +1,194 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::FromRequestParts,
+    http::{
+        header::{COOKIE, SET_COOKIE},
+        request::Parts,
+        HeaderMap, HeaderValue, Method, Request,
+    },
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use tracing::Instrument;
+
+use crate::{errors::CustomError, logging::query_span};
+
+// Set after a write so the same client keeps reading from the primary until
+// the replicas have had time to catch up.
+const STICKY_COOKIE: &str = "read_primary_until";
+
+// How often each replica is checked.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+// Seconds the replica is behind the primary; 0 when it has replayed
+// everything it received, or is not a standby at all.
+const LAG_SQL: &str = "SELECT COALESCE(CASE WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 \
+                       ELSE EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) END, 0)::float8";
+
+pub struct Replicas {
+    primary: PgPool,
+    replicas: Vec<PgPool>,
+    // Whether each replica answered the last check in time to be read from.
+    healthy: Vec<AtomicBool>,
+    next: AtomicUsize,
+    sticky: Duration,
+}
+
+impl Replicas {
+    // Replica pools connect lazily so a replica that is down at startup only
+    // sends reads to the primary instead of stopping the server. Replicas are
+    // read from once a check finds them healthy; see `watch`.
+    pub fn new(primary: PgPool, urls: &[String], sticky: Duration) -> anyhow::Result<Self> {
+        let replicas = urls
+            .iter()
+            .map(|url| {
+                PgPoolOptions::new()
+                    .max_connections(50)
+                    .acquire_timeout(Duration::from_secs(1))
+                    .connect_lazy(url)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let healthy = replicas.iter().map(|_| AtomicBool::new(false)).collect();
+        Ok(Self { primary, replicas, healthy, next: AtomicUsize::new(0), sticky })
+    }
+
+    // The next healthy replica in turn, or the primary when none is. Never
+    // waits on a replica; the checks in `watch` keep the health current.
+    fn pick(&self) -> (PgPool, ReadSource) {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..self.replicas.len())
+            .map(|offset| (start + offset) % self.replicas.len())
+            .find(|&index| self.healthy[index].load(Ordering::Relaxed))
+            .map_or((self.primary.clone(), ReadSource::Primary), |index| (self.replicas[index].clone(), ReadSource::Replica))
+    }
+
+    // Checks one replica: usable when it answers and is no further behind the
+    // primary than the sticky window, since clients only read their own
+    // writes from the primary for that long.
+    async fn check(&self, index: usize) {
+        let lag: Result<f64, _> = sqlx::query_scalar(LAG_SQL)
+            .fetch_one(&self.replicas[index])
+            .instrument(query_span(LAG_SQL, None))
+            .await;
+        let usable = match lag {
+            Ok(lag) => lag <= self.sticky.as_secs_f64(),
+            Err(_) => false,
+        };
+
+        if self.healthy[index].swap(usable, Ordering::Relaxed) != usable {
+            match (usable, lag) {
+                (true, _) => tracing::info!(replica = index, "read replica available"),
+                (false, Ok(lag)) => tracing::warn!(replica = index, lag, "read replica lagging"),
+                (false, Err(error)) => tracing::warn!(replica = index, "read replica unavailable: {error}"),
+            }
+        }
+    }
+}
+
+// Checks the replicas in the background so reads can pick one without
+// waiting. Does nothing without replicas.
+pub fn watch(replicas: Arc<Replicas>) {
+    if replicas.replicas.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            for index in 0..replicas.replicas.len() {
+                replicas.check(index).await;
+            }
+        }
+    });
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn sticky_until(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == STICKY_COOKIE)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+// Where a read was sent, which decides what the profile cache may do with it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ReadSource {
+    // The caller wrote recently and must see its writes, so the cache, which
+    // may hold an entry from before them, is bypassed.
+    Sticky,
+    Primary,
+    // Possibly behind the primary; not cached, or it could outlive a newer
+    // write.
+    Replica,
+}
+
+impl ReadSource {
+    pub fn may_read_cache(self) -> bool {
+        self != Self::Sticky
+    }
+
+    pub fn may_fill_cache(self) -> bool {
+        self != Self::Replica
+    }
+}
+
+// The pool read-only handlers query: a healthy replica when one is configured
+// and the caller has not written recently, the primary otherwise.
+pub struct ReadPool(pub PgPool, pub ReadSource);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ReadPool {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(replicas) = Extension::<Arc<Replicas>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| CustomError::InternalServerError)?;
+
+        if sticky_until(&parts.headers).is_some_and(|until| until > now_secs()) {
+            return Ok(ReadPool(replicas.primary.clone(), ReadSource::Sticky));
+        }
+
+        let (pool, source) = replicas.pick();
+        Ok(ReadPool(pool, source))
+    }
+}
+
+// Hands clients that just wrote a cookie pinning their reads to the primary.
+pub async fn read_your_writes<B>(
+    Extension(replicas): Extension<Arc<Replicas>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let write = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let mut response = next.run(req).await;
+
+    if write && !replicas.replicas.is_empty() && response.status().is_success() {
+        let secs = replicas.sticky.as_secs();
+        let cookie = format!("{STICKY_COOKIE}={}; Max-Age={secs}; Path=/; HttpOnly; SameSite=Lax", now_secs() + secs);
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(SET_COOKIE, cookie);
+        }
+    }
+
+    response
+}
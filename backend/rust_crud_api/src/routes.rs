@@ -0,0 +1,93 @@
+use axum::{
+    http::{HeaderValue, Request},
+    middleware::{self, Next},
+    response::Response,
+    routing::{delete, get, post},
+    Router
+};
+
+use crate::{api_keys, batch, cache, custom_fields, gdpr, history, idempotency, jobs, lifecycle, merges, pii, stats, tenants, verification, views};
+
+// Date after which the unversioned routes will be removed.
+const LEGACY_SUNSET: &str = "Wed, 30 Jun 2027 23:59:59 GMT";
+
+pub fn router() -> Router {
+    Router::new()
+        .nest("/v1", v1())
+        .nest("/v2", v2())
+        .merge(legacy().layer(middleware::from_fn(deprecated)))
+}
+
+// The original routes, still served unversioned as deprecated aliases.
+fn legacy() -> Router {
+    Router::new()
+        .route("/profiles", get(views::all_profiles))
+        .route("/profile", post(views::post_profile).layer(middleware::from_fn(idempotency::idempotent)))
+        .route("/profile/:id", get(views::profile).put(views::update_profile).delete(views::delete_profile))
+}
+
+fn v1() -> Router {
+    legacy()
+        .route("/profiles/batch", post(batch::batch_profiles).layer(middleware::from_fn(idempotency::idempotent)))
+        .route("/profiles/stats", get(stats::counts))
+        .route("/profiles/stats/timeseries", get(stats::timeseries))
+        .route("/profiles/duplicates", get(merges::candidates))
+        .route("/profile/:id/export", get(gdpr::export_profile))
+        .route("/profile/:id/erasure", post(gdpr::erase_profile))
+        .route("/profile/:id/diff", get(history::diff))
+        .route("/profile/:id/transitions", get(lifecycle::history).post(lifecycle::transition))
+        .route("/profile/:id/verify/:channel", post(verification::issue))
+        .route("/profile/:id/verify/:channel/confirm", post(verification::confirm))
+        .route("/profile/:id/merge", post(merges::merge))
+        .merge(admin())
+}
+
+fn v2() -> Router {
+    Router::new()
+        .route("/employees", get(views::all_profiles).post(views::post_profile).layer(middleware::from_fn(idempotency::idempotent)))
+        .route("/employees/batch", post(batch::batch_profiles).layer(middleware::from_fn(idempotency::idempotent)))
+        .route("/employees/stats", get(stats::counts))
+        .route("/employees/stats/timeseries", get(stats::timeseries))
+        .route("/employees/duplicates", get(merges::candidates))
+        .route("/employees/:id", get(views::profile).put(views::update_profile).delete(views::delete_profile))
+        .route("/employees/:id/export", get(gdpr::export_profile))
+        .route("/employees/:id/erasure", post(gdpr::erase_profile))
+        .route("/employees/:id/diff", get(history::diff))
+        .route("/employees/:id/transitions", get(lifecycle::history).post(lifecycle::transition))
+        .route("/employees/:id/verify/:channel", post(verification::issue))
+        .route("/employees/:id/verify/:channel/confirm", post(verification::confirm))
+        .route("/employees/:id/merge", post(merges::merge))
+        .merge(admin())
+}
+
+fn admin() -> Router {
+    Router::new()
+        .route("/admin/api-keys", get(api_keys::list_keys).post(api_keys::create_key))
+        .route("/admin/api-keys/:id", delete(api_keys::revoke_key))
+        .route("/admin/api-keys/:id/rotate", post(api_keys::rotate_key))
+        .route("/admin/tenants", get(tenants::list_tenants).post(tenants::create_tenant))
+        .route("/admin/tenants/:slug/profiles", get(tenants::tenant_profiles))
+        .route("/admin/pii/reencrypt", post(pii::reencrypt))
+        .route("/admin/cache", get(cache::cache_stats))
+        .route("/admin/custom-fields", get(custom_fields::list_fields).post(custom_fields::create_field))
+        .route("/admin/custom-fields/:name", delete(custom_fields::delete_field))
+        .route("/admin/jobs", get(jobs::list_jobs))
+        .route("/admin/jobs/runs", get(jobs::list_runs))
+        .route("/admin/jobs/:name/run", post(jobs::trigger_job))
+}
+
+// Marks responses served from the unversioned aliases as deprecated and
+// points clients at the /v1 equivalent.
+async fn deprecated<B>(req: Request<B>, next: Next<B>) -> Response {
+    let successor = format!("</v1{}>; rel=\"successor-version\"", req.uri().path());
+    let mut response = next.run(req).await;
+
+    let headers = response.headers_mut();
+    headers.insert("Deprecation", HeaderValue::from_static("true"));
+    headers.insert("Sunset", HeaderValue::from_static(LEGACY_SUNSET));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert("Link", link);
+    }
+
+    response
+}
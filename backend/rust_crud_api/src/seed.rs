@@ -0,0 +1,205 @@
+use chrono::{Duration, NaiveDate};
+use clap::ValueEnum;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::{
+    custom_fields::{self, CustomField, FieldKind},
+    errors::CustomError,
+    logging::query_span,
+    models::NewProfile,
+    pii::Pii,
+    tenants::Tenant,
+    views,
+};
+
+const FIRST_NAMES: &[&str] = &[
+    "Aarav", "Amara", "Ana", "Carlos", "Chen", "Chloe", "Daniel", "Emma", "Fatima", "Grace",
+    "Hana", "Ibrahim", "Isabella", "James", "Kenji", "Leila", "Liam", "Lucia", "Mateo", "Mei",
+    "Mohammed", "Nadia", "Noah", "Olivia", "Priya", "Rafael", "Sofia", "Tomasz", "Yusuf", "Zara",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Adeyemi", "Andersson", "Brown", "Chen", "Costa", "Dubois", "Garcia", "Haddad", "Ivanova", "Johnson",
+    "Kim", "Kowalski", "Martin", "Mensah", "Murphy", "Nakamura", "Nguyen", "Novak", "O'Brien", "Okafor",
+    "Patel", "Rossi", "Schmidt", "Silva", "Singh", "Smith", "Tanaka", "Williams", "Yilmaz", "Zhang",
+];
+
+// Reserved for documentation, so generated addresses never reach anyone.
+const DOMAINS: &[&str] = &["example.com", "example.org", "example.net"];
+
+// Rows committed per transaction, so large seeds do not hold one huge
+// transaction open.
+const CHUNK: usize = 1000;
+
+// Dataset sizes for common uses.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Preset {
+    /// A handful of rows for local development
+    Dev,
+    /// Enough rows for demos and paging through lists
+    Demo,
+    /// A large table for load tests
+    Load,
+}
+
+impl Preset {
+    pub fn count(self) -> usize {
+        match self {
+            Self::Dev => 25,
+            Self::Demo => 1_000,
+            Self::Load => 100_000,
+        }
+    }
+}
+
+// Produces the same sequence of fake employees for the same seed.
+pub struct Generator {
+    rng: ChaCha8Rng,
+    sequence: usize,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: ChaCha8Rng::seed_from_u64(seed), sequence: 0 }
+    }
+
+    // The next employee, given the id it will be stored under and the custom
+    // fields to fill in.
+    pub fn profile(&mut self, id: i32, fields: &[CustomField]) -> NewProfile {
+        self.sequence += 1;
+        let first = *FIRST_NAMES.choose(&mut self.rng).unwrap_or(&"Alex");
+        let last = *LAST_NAMES.choose(&mut self.rng).unwrap_or(&"Doe");
+        let domain = *DOMAINS.choose(&mut self.rng).unwrap_or(&"example.com");
+        let local = format!("{first}.{last}").to_lowercase().replace('\'', "");
+        // 555-0100 to 555-0199 are reserved for fictional use.
+        let econtact = format!("+1 {}-555-01{:02}", self.rng.gen_range(201..990), self.rng.gen_range(0..100));
+        let custom = fields.iter().filter_map(|field| Some((field.name.clone(), self.value(field)?))).collect();
+
+        NewProfile {
+            id,
+            eid: format!("E{id:06}"),
+            ename: format!("{first} {last}"),
+            // The sequence number keeps addresses unique within a seed run.
+            eemail: format!("{local}{}@{domain}", self.sequence),
+            econtact,
+            custom: Some(custom),
+        }
+    }
+
+    // A value of the field's kind within its bounds, or None when none can be
+    // made, e.g. for a string the pattern does not accept.
+    fn value(&mut self, field: &CustomField) -> Option<Value> {
+        // Without one of the bounds, the range reaches 100 past the other.
+        let (min, max) = match (field.min, field.max) {
+            (Some(min), Some(max)) => (min, max.max(min)),
+            (Some(min), None) => (min, min + 100.0),
+            (None, Some(max)) => (max - 100.0, max),
+            (None, None) => (0.0, 100.0),
+        };
+        let value = match field.kind {
+            FieldKind::String => {
+                let word = *LAST_NAMES.choose(&mut self.rng)?;
+                Value::from(word.repeat(min as usize / word.len() + 1).chars().take(max as usize).collect::<String>())
+            }
+            FieldKind::Integer => {
+                let (low, high) = (min.ceil() as i64, max.floor() as i64);
+                Value::from(self.rng.gen_range(low..=high.max(low)))
+            }
+            FieldKind::Number => Value::from(self.rng.gen_range(min..=max)),
+            FieldKind::Boolean => Value::Bool(self.rng.gen()),
+            FieldKind::Date => {
+                let start = NaiveDate::from_ymd_opt(2000, 1, 1)?;
+                Value::String((start + Duration::days(self.rng.gen_range(0..9000))).to_string())
+            }
+            FieldKind::Enum => Value::String(field.options.as_ref()?.choose(&mut self.rng)?.clone()),
+        };
+
+        field.coerce(&value)
+    }
+}
+
+// Inserts `count` generated employees for the tenant, numbered after the
+// tenant's highest existing id, and returns the ids used (empty for 0). The
+// tenant's custom fields get generated values; a required string field whose
+// pattern the generated text does not match fails the seed.
+pub async fn seed(pool: &PgPool, tenant: &Tenant, pii: &Pii, seed: u64, count: usize) -> Result<std::ops::RangeInclusive<i32>, CustomError> {
+    let mut generator = Generator::new(seed);
+    let mut tx = tenant.begin(pool).await?;
+    let fields = custom_fields::fields(&mut tx, tenant).await?;
+    let sql = "SELECT COALESCE(MAX(id), 0) FROM employee WHERE tenant_id=$1";
+    let start: i32 = sqlx::query_scalar(sql)
+    .bind(tenant.id)
+    .fetch_one(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    let count = i32::try_from(count).map_err(|_| CustomError::BadRequest)?;
+    let last = start.checked_add(count).ok_or(CustomError::BadRequest)?;
+    // Empty for 0, without computing start + 1, which overflows at i32::MAX.
+    #[allow(clippy::reversed_empty_ranges)]
+    let ids = if count == 0 { 1..=0 } else { start + 1..=last };
+
+    let mut pending = ids.clone().peekable();
+    while pending.peek().is_some() {
+        let mut tx = tenant.begin(pool).await?;
+        for id in pending.by_ref().take(CHUNK) {
+            views::insert_profile(&mut tx, tenant, pii, &generator.profile(id, &fields)).await?;
+        }
+        tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    }
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn field(kind: FieldKind, min: Option<f64>, max: Option<f64>) -> CustomField {
+        CustomField {
+            id: 1,
+            name: "field".to_string(),
+            kind,
+            required: true,
+            pattern: None,
+            min,
+            max,
+            options: None,
+            created_at: Utc::now(),
+            regex: Default::default(),
+        }
+    }
+
+    #[test]
+    fn generates_numbers_within_one_sided_bounds() {
+        let mut generator = Generator::new(1);
+        for kind in [FieldKind::Number, FieldKind::Integer] {
+            let below = field(kind, None, Some(-5.0));
+            let above = field(kind, Some(1000.0), None);
+            for _ in 0..50 {
+                let value = generator.value(&below).unwrap().as_f64().unwrap();
+                assert!((-105.0..=-5.0).contains(&value), "{value}");
+                let value = generator.value(&above).unwrap().as_f64().unwrap();
+                assert!((1000.0..=1100.0).contains(&value), "{value}");
+            }
+        }
+    }
+
+    #[test]
+    fn generates_strings_within_length_bounds() {
+        let mut generator = Generator::new(1);
+        let code = field(FieldKind::String, Some(12.0), Some(15.0));
+        for _ in 0..50 {
+            let value = generator.value(&code).unwrap();
+            assert!((12..=15).contains(&value.as_str().unwrap().len()));
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use axum::extract::Query;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{Postgres, QueryBuilder, Row};
+use tracing::Instrument;
+
+use crate::{
+    custom_fields,
+    errors::CustomError,
+    logging::query_span,
+    negotiate::{Format, Negotiated},
+    replicas::ReadPool,
+    tenants::Tenant,
+};
+
+const MAX_DIMENSIONS: usize = 4;
+const MAX_POINTS: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    // Comma-separated: `email_domain`, `status`, `created_month`,
+    // `department` or `custom.<field>`. Defaults to `status`.
+    pub group_by: Option<String>,
+}
+
+// Employee counts per combination of dimension values, largest first.
+#[derive(Serialize)]
+pub struct Counts {
+    pub dimensions: Vec<String>,
+    pub total: i64,
+    pub groups: Vec<Map<String, Value>>,
+}
+
+#[tracing::instrument(name = "stats.counts", skip_all)]
+pub async fn counts(Query(query): Query<StatsQuery>, format: Format, tenant: Tenant, ReadPool(pool, _): ReadPool) -> Result<Negotiated<Counts>, CustomError> {
+    let dimensions: Vec<String> = query
+        .group_by
+        .as_deref()
+        .unwrap_or("status")
+        .split(',')
+        .map(|dimension| dimension.trim().to_string())
+        .filter(|dimension| !dimension.is_empty())
+        .collect();
+    let distinct = dimensions.iter().enumerate().all(|(i, dimension)| !dimensions[..i].contains(dimension));
+    if dimensions.is_empty() || dimensions.len() > MAX_DIMENSIONS || !distinct {
+        return Err(CustomError::BadRequest);
+    }
+
+    let mut tx = tenant.begin(&pool).await?;
+    let fields = custom_fields::fields(&mut tx, &tenant).await?;
+
+    let mut query = QueryBuilder::<Postgres>::new("SELECT ");
+    for dimension in &dimensions {
+        let column = match dimension.as_str() {
+            "email_domain" => Some("eemail_domain"),
+            "status" => Some("status"),
+            "created_month" => Some("to_char(created_at AT TIME ZONE 'UTC', 'YYYY-MM')"),
+            _ => None,
+        };
+        if let Some(column) = column {
+            query.push(column).push(", ");
+            continue;
+        }
+
+        // Departments are kept in a custom field of that name.
+        let name = match dimension.as_str() {
+            "department" => "department",
+            other => other.strip_prefix("custom.").ok_or(CustomError::BadRequest)?,
+        };
+        if !fields.iter().any(|field| field.name == name) {
+            return Err(CustomError::BadRequest);
+        }
+        query.push("custom->>").push_bind(name.to_string()).push(", ");
+    }
+
+    let positions: Vec<String> = (1..=dimensions.len()).map(|position| position.to_string()).collect();
+    query
+        .push("count(*) FROM employee WHERE tenant_id=")
+        .push_bind(tenant.id)
+        .push(" AND erased_at IS NULL GROUP BY ")
+        .push(positions.join(", "))
+        .push(" ORDER BY count(*) DESC, ")
+        .push(positions.join(", "));
+
+    let sql = query.sql().to_string();
+    let rows = query
+    .build()
+    .fetch_all(&mut tx)
+    .instrument(query_span(&sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    let mut total = 0;
+    let mut groups = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut group = Map::new();
+        for (i, dimension) in dimensions.iter().enumerate() {
+            let value: Option<String> = row.try_get(i).map_err(|_| CustomError::InternalServerError)?;
+            group.insert(dimension.clone(), value.map_or(Value::Null, Value::String));
+        }
+        let count: i64 = row.try_get(dimensions.len()).map_err(|_| CustomError::InternalServerError)?;
+        group.insert("count".to_string(), count.into());
+        total += count;
+        groups.push(group);
+    }
+
+    Ok(Negotiated(format, Counts { dimensions, total, groups }))
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    Day,
+    Week,
+    #[default]
+    Month,
+}
+
+impl Interval {
+    fn as_str(self) -> &'static str {
+        match self {
+            Interval::Day => "day",
+            Interval::Week => "week",
+            Interval::Month => "month",
+        }
+    }
+
+    fn approximate(self) -> Duration {
+        match self {
+            Interval::Day => Duration::days(1),
+            Interval::Week => Duration::weeks(1),
+            Interval::Month => Duration::days(28),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SeriesQuery {
+    #[serde(default)]
+    pub interval: Interval,
+    // Defaults to a year before `to`.
+    pub from: Option<DateTime<Utc>>,
+    // Defaults to now.
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Point {
+    pub period: NaiveDate,
+    pub hires: i64,
+    pub removals: i64,
+    pub terminations: i64,
+}
+
+#[derive(Serialize)]
+pub struct Series {
+    pub interval: Interval,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub points: Vec<Point>,
+}
+
+// Hires, removals and terminations per period, in UTC. Hires and removals
+// come from the employee history, so deleted employees still count; employees
+// that existed before history was kept count as hired when it started.
+// Terminations are counted on their effective date.
+#[tracing::instrument(name = "stats.timeseries", skip_all)]
+pub async fn timeseries(Query(query): Query<SeriesQuery>, format: Format, tenant: Tenant, ReadPool(pool, _): ReadPool) -> Result<Negotiated<Series>, CustomError> {
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::days(365));
+    if from > to || (to - from).num_seconds() / query.interval.approximate().num_seconds() > MAX_POINTS {
+        return Err(CustomError::BadRequest);
+    }
+
+    let sql = "WITH periods AS (\
+                   SELECT generate_series(date_trunc($2, $3 AT TIME ZONE 'UTC'), $4 AT TIME ZONE 'UTC', ('1 ' || $2)::interval) AS period\
+               ), latest AS (\
+                   SELECT DISTINCT ON (employee_id) (data->>'created_at')::timestamptz AS created_at, valid_to AS removed_at \
+                   FROM employee_history WHERE tenant_id=$1 AND merged_from IS NULL ORDER BY employee_id, valid_from DESC\
+               ), hires AS (\
+                   SELECT date_trunc($2, created_at AT TIME ZONE 'UTC') AS period, count(*) AS hires FROM latest GROUP BY 1\
+               ), removals AS (\
+                   SELECT date_trunc($2, removed_at AT TIME ZONE 'UTC') AS period, count(*) AS removals FROM latest WHERE removed_at IS NOT NULL GROUP BY 1\
+               ), terminations AS (\
+                   SELECT date_trunc($2, effective_date::timestamp) AS period, count(*) AS terminations FROM employee_status_transition \
+                   WHERE tenant_id=$1 AND to_status='terminated' GROUP BY 1\
+               ) \
+               SELECT periods.period::date AS period, COALESCE(hires, 0) AS hires, COALESCE(removals, 0) AS removals, COALESCE(terminations, 0) AS terminations \
+               FROM periods LEFT JOIN hires USING (period) LEFT JOIN removals USING (period) LEFT JOIN terminations USING (period) \
+               ORDER BY periods.period";
+
+    let mut tx = tenant.begin(&pool).await?;
+    let points = sqlx::query_as(sql)
+    .bind(tenant.id)
+    .bind(query.interval.as_str())
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    Ok(Negotiated(format, Series { interval: query.interval, from, to, points }))
+}
@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::{header::HOST, request::Parts, StatusCode},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::Instrument;
+
+use crate::{
+    api_keys::{Admin, ApiKey, GlobalAdmin},
+    config::Config,
+    errors::CustomError,
+    logging::query_span,
+    models::Profile,
+    pii::{Pii, PiiAccess},
+    tokens::{bearer, Claims},
+};
+
+// The tenant a request acts on. Every employee query is scoped to it. It is
+// the tenant of the caller's API key or bearer token; keys without a tenant
+// pick one with `X-Tenant-Id` or the subdomain, and anonymous callers only
+// get DEFAULT_TENANT.
+#[derive(Clone)]
+pub struct Tenant {
+    pub id: i32,
+    rls: bool,
+}
+
+impl Tenant {
+    // Starts a transaction for the tenant's queries. With row-level security
+    // enabled the tenant id is also handed to Postgres for the transaction.
+    pub async fn begin(&self, pool: &PgPool) -> Result<Transaction<'static, Postgres>, CustomError> {
+        let mut tx = pool.begin().await.map_err(|_| CustomError::InternalServerError)?;
+
+        if self.rls {
+            let sql = "SELECT set_config('app.tenant_id', $1, true)";
+            sqlx::query(sql)
+                .bind(self.id.to_string())
+                .execute(&mut tx)
+                .instrument(query_span(sql, None))
+                .await
+                .map_err(|_| CustomError::InternalServerError)?;
+        }
+
+        Ok(tx)
+    }
+
+    pub async fn from_slug(pool: &PgPool, config: &Config, slug: &str) -> Result<Self, CustomError> {
+        Ok(Tenant { id: tenant_id(pool, slug).await?, rls: config.tenant_rls })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Tenant {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(tenant) = parts.extensions.get::<Tenant>() {
+            return Ok(tenant.clone());
+        }
+
+        let Extension(config) = Extension::<Arc<Config>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| CustomError::InternalServerError)?;
+        let Extension(pool) = Extension::<PgPool>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| CustomError::InternalServerError)?;
+
+        let key = match parts.headers.contains_key("X-Api-Key") {
+            true => Some(ApiKey::from_request_parts(parts, state).await?),
+            false => None,
+        };
+        let claims = match bearer(&parts.headers) {
+            Some(_) => Some(Claims::from_request_parts(parts, state).await?),
+            None => None,
+        };
+        let requested = requested_slug(parts, config.tenant_base_domain.as_deref());
+
+        // Only credentials decide which tenant a caller belongs to; the header
+        // and subdomain may repeat that tenant but never pick another one.
+        let mut bound = key.as_ref().and_then(|key| key.tenant_id);
+        if let Some(slug) = claims.as_ref().and_then(|claims| claims.tenant.as_deref()) {
+            let claimed = tenant_id(&pool, slug).await?;
+            if bound.is_some_and(|bound| bound != claimed) {
+                return Err(CustomError::Forbidden);
+            }
+            bound = Some(claimed);
+        }
+        let default = match &config.default_tenant {
+            Some(slug) => Some(tenant_id(&pool, slug).await?),
+            None => None,
+        };
+
+        let id = match (bound, requested) {
+            (Some(bound), Some(slug)) => {
+                if tenant_id(&pool, &slug).await? != bound {
+                    return Err(CustomError::Forbidden);
+                }
+                bound
+            }
+            (Some(bound), None) => bound,
+            // Keys without a tenant may act on any tenant.
+            (None, Some(slug)) if key.is_some() => tenant_id(&pool, &slug).await?,
+            (None, Some(slug)) => {
+                let id = tenant_id(&pool, &slug).await?;
+                if Some(id) != default {
+                    return Err(CustomError::Unauthorized);
+                }
+                id
+            }
+            (None, None) => default.ok_or(CustomError::BadRequest)?,
+        };
+
+        let tenant = Tenant { id, rls: config.tenant_rls };
+        parts.extensions.insert(tenant.clone());
+        Ok(tenant)
+    }
+}
+
+// The tenant named by the `X-Tenant-Id` header, or else by the subdomain of
+// the configured base domain (`acme.api.example.com` -> `acme`).
+fn requested_slug(parts: &Parts, base_domain: Option<&str>) -> Option<String> {
+    if let Some(slug) = parts.headers.get("X-Tenant-Id").and_then(|v| v.to_str().ok()) {
+        return Some(slug.to_string());
+    }
+
+    let host = parts.headers.get(HOST)?.to_str().ok()?;
+    let host = host.split(':').next()?;
+    let subdomain = host.strip_suffix(base_domain?)?.strip_suffix('.')?;
+    (!subdomain.is_empty() && !subdomain.contains('.')).then(|| subdomain.to_string())
+}
+
+pub async fn tenant_id(pool: &PgPool, slug: &str) -> Result<i32, CustomError> {
+    let sql = "SELECT id FROM tenant WHERE slug=$1";
+    sqlx::query_scalar(sql)
+        .bind(slug)
+        .fetch_optional(pool)
+        .instrument(query_span(sql, None))
+        .await
+        .map_err(|_| CustomError::InternalServerError)?
+        .ok_or(CustomError::BadRequest)
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct TenantInfo {
+    pub id: i32,
+    pub slug: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct NewTenant {
+    pub slug: String,
+    pub name: String,
+}
+
+pub async fn list_tenants(_: GlobalAdmin, Extension(pool): Extension<PgPool>) -> Result<Json<Vec<TenantInfo>>, CustomError> {
+    let sql = "SELECT id, slug, name, created_at FROM tenant ORDER BY id";
+    let tenants = sqlx::query_as(sql).fetch_all(&pool).instrument(query_span(sql, None)).await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    Ok(Json(tenants))
+}
+
+pub async fn create_tenant(_: GlobalAdmin, Extension(pool): Extension<PgPool>, Json(data): Json<NewTenant>) -> Result<(StatusCode, Json<TenantInfo>), CustomError> {
+    let valid = !data.slug.is_empty()
+        && data.slug.len() <= 63
+        && data.slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(CustomError::BadRequest);
+    }
+
+    let sql = "INSERT INTO tenant (slug, name) values ($1, $2) RETURNING id, slug, name, created_at";
+    let tenant = sqlx::query_as(sql)
+    .bind(&data.slug)
+    .bind(&data.name)
+    .fetch_one(&pool)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::BadRequest
+    })?;
+
+    Ok((StatusCode::CREATED, Json(tenant)))
+}
+
+// Admin keys bound to a tenant can only read their own tenant's profiles.
+pub async fn tenant_profiles(Admin(admin): Admin, Path(slug): Path<String>, Extension(config): Extension<Arc<Config>>, Extension(pii): Extension<Arc<Pii>>, Extension(pool): Extension<PgPool>) -> Result<Json<Vec<Profile>>, CustomError> {
+    let tenant = Tenant::from_slug(&pool, &config, &slug).await?;
+    if admin.tenant_id.is_some_and(|bound| bound != tenant.id) {
+        return Err(CustomError::Forbidden);
+    }
+
+    let mut tx = tenant.begin(&pool).await?;
+    let sql = "SELECT * FROM employee WHERE tenant_id=$1 ORDER BY id";
+    let mut profiles: Vec<Profile> = sqlx::query_as(sql)
+    .bind(tenant.id)
+    .fetch_all(&mut tx)
+    .instrument(query_span(sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    for profile in &mut profiles {
+        pii.reveal(profile, &PiiAccess(true))?;
+    }
+
+    Ok(Json(profiles))
+}
@@ -0,0 +1,217 @@
+use std::{
+    fs::{self, File},
+    future::Future,
+    io::{self, BufReader},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use axum::{extract::FromRequestParts, http::request::Parts};
+use axum_server::{
+    accept::Accept,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use hyper::server::conn::AddrStream;
+use rustls::{
+    server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient},
+    Certificate, PrivateKey, RootCertStore, ServerConfig,
+};
+use sha2::{Digest, Sha256};
+use tokio_rustls::server::TlsStream;
+use tonic::transport::{Identity, ServerTlsConfig};
+use tower_http::add_extension::AddExtension;
+
+use crate::{config::Config, errors::CustomError};
+
+// The verified client certificate presented during the TLS handshake.
+#[derive(Clone)]
+pub struct ClientIdentity {
+    pub subject: String,
+    pub common_name: Option<String>,
+    pub fingerprint: String,
+}
+
+impl ClientIdentity {
+    pub(crate) fn from_der(der: &[u8]) -> Self {
+        let fingerprint = hex::encode(Sha256::digest(der));
+        let (subject, common_name) = match x509_parser::parse_x509_certificate(der) {
+            Ok((_, parsed)) => (
+                parsed.subject().to_string(),
+                parsed
+                    .subject()
+                    .iter_common_name()
+                    .next()
+                    .and_then(|cn| cn.as_str().ok())
+                    .map(String::from),
+            ),
+            Err(_) => (String::new(), None),
+        };
+
+        Self { subject, common_name, fingerprint }
+    }
+}
+
+// Rejects requests made without a client certificate.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIdentity {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Option<ClientIdentity>>()
+            .cloned()
+            .flatten()
+            .ok_or(CustomError::Unauthorized)
+    }
+}
+
+// Terminates TLS and hands the client certificate, if any, to every request
+// on the connection.
+#[derive(Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl ClientCertAcceptor {
+    pub fn new(config: RustlsConfig) -> Self {
+        Self { inner: RustlsAcceptor::new(config) }
+    }
+}
+
+impl<S: Send + 'static> Accept<AddrStream, S> for ClientCertAcceptor {
+    type Stream = TlsStream<AddrStream>;
+    type Service = AddExtension<S, Option<ClientIdentity>>;
+    type Future = Pin<Box<dyn Future<Output = io::Result<(Self::Stream, Self::Service)>> + Send>>;
+
+    fn accept(&self, stream: AddrStream, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            let identity = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| ClientIdentity::from_der(&cert.0));
+            if let Some(identity) = &identity {
+                tracing::debug!(subject = %identity.subject, cn = ?identity.common_name, "client certificate accepted");
+            }
+
+            Ok((stream, AddExtension::new(service, identity)))
+        })
+    }
+}
+
+// Builds the rustls settings, or `None` when TLS is not configured.
+pub fn server_config(config: &Config) -> anyhow::Result<Option<Arc<ServerConfig>>> {
+    let (cert_path, key_path) = match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return Ok(None),
+        _ => anyhow::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+    };
+
+    let certs = read_certs(cert_path).context("Invalid TLS_CERT_PATH")?;
+    let key = read_key(key_path).context("Invalid TLS_KEY_PATH")?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match &config.tls_client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(ca_path).context("Invalid TLS_CLIENT_CA_PATH")? {
+                roots.add(&cert).context("Invalid TLS_CLIENT_CA_PATH")?;
+            }
+            let verifier = if config.tls_client_auth_optional {
+                AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed()
+            } else {
+                AllowAnyAuthenticatedClient::new(roots).boxed()
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder.with_single_cert(certs, key).context("Invalid TLS certificate or key")?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(Some(Arc::new(server_config)))
+}
+
+// The same certificate, key and client CA for the gRPC server, which reads
+// them once at startup rather than reloading them.
+pub fn grpc_config(config: &Config) -> anyhow::Result<Option<ServerTlsConfig>> {
+    // Checks the files as the REST API does, with the same messages.
+    if server_config(config)?.is_none() {
+        return Ok(None);
+    }
+    let (Some(cert_path), Some(key_path)) = (&config.tls_cert_path, &config.tls_key_path) else {
+        return Ok(None);
+    };
+
+    let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(fs::read(cert_path)?, fs::read(key_path)?));
+    if let Some(ca_path) = &config.tls_client_ca_path {
+        tls = tls
+            .client_ca_root(tonic::transport::Certificate::from_pem(fs::read(ca_path)?))
+            .client_auth_optional(config.tls_client_auth_optional);
+    }
+
+    Ok(Some(tls))
+}
+
+fn read_certs(path: &str) -> anyhow::Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+    anyhow::ensure!(!certs.is_empty(), "no certificates found in {path}");
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &str) -> anyhow::Result<PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        if let rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key) = item {
+            return Ok(PrivateKey(key));
+        }
+    }
+    anyhow::bail!("no private key found in {path}")
+}
+
+// Polls the certificate files and swaps in the new settings when they change,
+// so renewed certificates apply to new connections without a restart. A bad
+// file is logged and the current settings stay in place.
+pub fn watch(tls: RustlsConfig, config: Arc<Config>) {
+    if config.tls_reload_secs == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.tls_reload_secs));
+        let mut seen = modified(&config);
+        loop {
+            interval.tick().await;
+            let current = modified(&config);
+            if current == seen {
+                continue;
+            }
+
+            match server_config(&config) {
+                Ok(Some(server_config)) => {
+                    tls.reload_from_config(server_config);
+                    seen = current;
+                    tracing::info!("reloaded TLS certificates");
+                }
+                Ok(None) => {}
+                Err(error) => tracing::warn!("keeping current TLS certificates: {error:#}"),
+            }
+        }
+    });
+}
+
+fn modified(config: &Config) -> Vec<Option<SystemTime>> {
+    [&config.tls_cert_path, &config.tls_key_path, &config.tls_client_ca_path]
+        .into_iter()
+        .flatten()
+        .map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
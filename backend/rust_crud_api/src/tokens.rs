@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+    Extension,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::{config::Config, errors::CustomError};
+
+// The claims of a bearer token signed with JWT_SECRET (HS256).
+#[derive(Clone, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    // Slug of the tenant the subject belongs to; the token can only act on it.
+    #[serde(default)]
+    pub tenant: Option<String>,
+    pub exp: i64,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Claims {
+    type Rejection = CustomError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(claims.clone());
+        }
+
+        let Extension(config) = Extension::<Arc<Config>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| CustomError::InternalServerError)?;
+
+        let token = bearer(&parts.headers).ok_or(CustomError::Unauthorized)?;
+        let secret = config.jwt_secret.as_deref().ok_or(CustomError::Unauthorized)?;
+        let claims = verify(secret.as_bytes(), token, Utc::now().timestamp()).ok_or(CustomError::Unauthorized)?;
+
+        tracing::debug!(sub = %claims.sub, "authenticated bearer token");
+        parts.extensions.insert(claims.clone());
+        Ok(claims)
+    }
+}
+
+// The token of an `Authorization: Bearer <token>` header.
+pub fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers.get(AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ")
+}
+
+// The claims of an HS256 token whose signature matches `secret` and which has
+// not expired at `now` (seconds since the epoch).
+pub fn verify(secret: &[u8], token: &str, now: i64) -> Option<Claims> {
+    let (signed, signature) = token.rsplit_once('.')?;
+    let (header, payload) = signed.split_once('.')?;
+
+    let header: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).ok()?).ok()?;
+    if header.get("alg")?.as_str()? != "HS256" {
+        return None;
+    }
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).ok()?;
+    mac.update(signed.as_bytes());
+    mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).ok()?).ok()?;
+
+    let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    (claims.exp > now).then_some(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &[u8], header: &str, payload: &str) -> String {
+        let signed = format!("{}.{}", URL_SAFE_NO_PAD.encode(header), URL_SAFE_NO_PAD.encode(payload));
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(signed.as_bytes());
+        format!("{signed}.{}", URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+
+    const HS256: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+    #[test]
+    fn accepts_a_valid_token() {
+        let token = sign(b"secret", HS256, r#"{"sub":"jane","tenant":"acme","exp":2000}"#);
+        let claims = verify(b"secret", &token, 1000).unwrap();
+        assert_eq!(claims.sub, "jane");
+        assert_eq!(claims.tenant.as_deref(), Some("acme"));
+    }
+
+    #[test]
+    fn rejects_another_secret() {
+        let token = sign(b"other", HS256, r#"{"sub":"jane","exp":2000}"#);
+        assert!(verify(b"secret", &token, 1000).is_none());
+    }
+
+    #[test]
+    fn rejects_a_changed_payload() {
+        let token = sign(b"secret", HS256, r#"{"sub":"jane","tenant":"acme","exp":2000}"#);
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let forged = format!(
+            "{}.{}.{signature}",
+            URL_SAFE_NO_PAD.encode(HS256),
+            URL_SAFE_NO_PAD.encode(r#"{"sub":"jane","tenant":"other","exp":2000}"#)
+        );
+        assert!(verify(b"secret", &forged, 1000).is_none());
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let token = sign(b"secret", HS256, r#"{"sub":"jane","exp":1000}"#);
+        assert!(verify(b"secret", &token, 1000).is_none());
+    }
+
+    #[test]
+    fn rejects_other_algorithms() {
+        let token = sign(b"secret", r#"{"alg":"none"}"#, r#"{"sub":"jane","exp":2000}"#);
+        assert!(verify(b"secret", &token, 1000).is_none());
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use tracing::Instrument;
+
+use crate::{
+    api_keys::Authenticated,
+    cache::ProfileCache,
+    config::Config,
+    errors::CustomError,
+    logging::query_span,
+    notify::{Channel, Message, Notifier},
+    pii::Pii,
+    tenants::Tenant,
+    views,
+};
+
+impl Channel {
+    // The encrypted value, its blind index and its verified-at column.
+    fn columns(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Channel::Email => ("eemail", "eemail_bidx", "eemail_verified_at"),
+            Channel::Phone => ("econtact", "econtact_bidx", "econtact_verified_at"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Issued {
+    pub channel: Channel,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct Confirmation {
+    pub code: String,
+}
+
+// An issued code, without the code itself.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Verification {
+    pub id: i32,
+    pub channel: Channel,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct Verified {
+    pub channel: Channel,
+    pub verified_at: DateTime<Utc>,
+}
+
+// Sends a one-time code to the employee's current email address or phone
+// number. Issuing a code replaces any earlier one for the same channel. A new
+// code can be requested every VERIFICATION_RESEND_SECS, and none once
+// VERIFICATION_DAILY_ATTEMPTS wrong guesses were made in the last 24 hours.
+#[tracing::instrument(name = "verification.issue", skip_all, fields(employee_id = id))]
+#[allow(clippy::too_many_arguments)]
+pub async fn issue(_: Authenticated, Path((id, channel)): Path<(i32, Channel)>, tenant: Tenant, Extension(config): Extension<Arc<Config>>, Extension(pii): Extension<Arc<Pii>>, Extension(notifier): Extension<Arc<dyn Notifier>>, Extension(pool): Extension<PgPool>) -> Result<(StatusCode, Json<Issued>), CustomError> {
+    let (value_column, bidx_column, _) = channel.columns();
+    let mut tx = tenant.begin(&pool).await?;
+    let id = views::resolve_id(&mut tx, &tenant, id).await?;
+
+    // The row lock makes concurrent requests for the employee take turns, so
+    // the limits below cannot be raced.
+    let sql = format!("SELECT {value_column}, {bidx_column} FROM employee WHERE id=$1 AND tenant_id=$2 AND erased_at IS NULL FOR UPDATE");
+    let (value, bidx): (String, Option<String>) = sqlx::query_as(&sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_one(&mut tx)
+    .instrument(query_span(&sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::TaskNotFound
+    })?;
+    let value = pii.decrypt(&value)?;
+    let bidx = bidx.ok_or(CustomError::Conflict)?;
+
+    let sql = "SELECT COALESCE(bool_or(created_at > now() - make_interval(secs => $4)), false), COALESCE(SUM(attempts), 0) \
+               FROM contact_verification WHERE employee_id=$1 AND tenant_id=$2 AND channel=$3 AND created_at > now() - interval '1 day'";
+    let (recent, attempts): (bool, i64) = sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .bind(channel)
+    .bind(config.verification_resend_secs as f64)
+    .fetch_one(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+    if recent || attempts >= config.verification_daily_attempts {
+        return Err(CustomError::TooManyRequests);
+    }
+
+    let sql = "UPDATE contact_verification SET expires_at=now() WHERE employee_id=$1 AND tenant_id=$2 AND channel=$3 AND confirmed_at IS NULL AND expires_at > now()";
+    sqlx::query(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .bind(channel)
+    .execute(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+    let sql = "INSERT INTO contact_verification (tenant_id, employee_id, channel, code_hash, target_bidx, expires_at) values ($1, $2, $3, $4, $5, now() + make_interval(secs => $6)) RETURNING id, expires_at";
+    let (verification_id, expires_at): (i32, DateTime<Utc>) = sqlx::query_as(sql)
+    .bind(tenant.id)
+    .bind(id)
+    .bind(channel)
+    .bind(pii.code_index(&code))
+    .bind(&bidx)
+    .bind(config.verification_code_ttl_secs as f64)
+    .fetch_one(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+
+    // Sent after committing so no lock is held while the message goes out; a
+    // code that could not be sent is withdrawn again.
+    let minutes = (config.verification_code_ttl_secs / 60).max(1);
+    let message = Message {
+        channel,
+        to: value,
+        subject: "Your verification code".to_string(),
+        body: format!("Your verification code is {code}. It expires in {minutes} minutes."),
+    };
+    if let Err(error) = notifier.send(&message).await {
+        tracing::warn!("could not send verification code: {error:#}");
+        let mut tx = tenant.begin(&pool).await?;
+        let sql = "DELETE FROM contact_verification WHERE id=$1";
+        sqlx::query(sql)
+        .bind(verification_id)
+        .execute(&mut tx)
+        .instrument(query_span(sql, Some(id)))
+        .await.map_err(|_| {
+            CustomError::InternalServerError
+        })?;
+        tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+        return Err(CustomError::InternalServerError);
+    }
+
+    Ok((StatusCode::ACCEPTED, Json(Issued { channel, expires_at })))
+}
+
+// Checks a code against the latest one issued. Wrong codes count towards
+// VERIFICATION_MAX_ATTEMPTS for the code and VERIFICATION_DAILY_ATTEMPTS
+// across codes; a code sent to a value that has since changed is rejected
+// with 409.
+#[tracing::instrument(name = "verification.confirm", skip_all, fields(employee_id = id))]
+#[allow(clippy::too_many_arguments)]
+pub async fn confirm(_: Authenticated, Path((id, channel)): Path<(i32, Channel)>, tenant: Tenant, Extension(config): Extension<Arc<Config>>, Extension(pii): Extension<Arc<Pii>>, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>, Json(data): Json<Confirmation>) -> Result<Json<Verified>, CustomError> {
+    let (_, bidx_column, verified_column) = channel.columns();
+    let mut tx = tenant.begin(&pool).await?;
+    let id = views::resolve_id(&mut tx, &tenant, id).await?;
+
+    let sql = "SELECT id, code_hash, target_bidx, attempts, \
+               (SELECT COALESCE(SUM(attempts), 0) FROM contact_verification \
+                WHERE employee_id=$1 AND tenant_id=$2 AND channel=$3 AND created_at > now() - interval '1 day') \
+               FROM contact_verification \
+               WHERE employee_id=$1 AND tenant_id=$2 AND channel=$3 AND confirmed_at IS NULL AND expires_at > now() \
+               ORDER BY id DESC LIMIT 1 FOR UPDATE";
+    let (verification_id, code_hash, target_bidx, attempts, daily_attempts): (i32, String, String, i32, i64) = sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .bind(channel)
+    .fetch_optional(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?
+    .ok_or(CustomError::TaskNotFound)?;
+
+    if attempts >= config.verification_max_attempts || daily_attempts >= config.verification_daily_attempts {
+        return Err(CustomError::TooManyRequests);
+    }
+
+    if pii.code_index(data.code.trim()) != code_hash {
+        let sql = "UPDATE contact_verification SET attempts=attempts + 1 WHERE id=$1";
+        sqlx::query(sql)
+        .bind(verification_id)
+        .execute(&mut tx)
+        .instrument(query_span(sql, Some(id)))
+        .await.map_err(|_| {
+            CustomError::InternalServerError
+        })?;
+        tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+        return Err(CustomError::UnprocessableEntity);
+    }
+
+    let sql = format!("UPDATE employee SET {verified_column}=now() WHERE id=$1 AND tenant_id=$2 AND {bidx_column}=$3 AND erased_at IS NULL RETURNING {verified_column}");
+    let verified_at = sqlx::query_scalar(&sql)
+    .bind(id)
+    .bind(tenant.id)
+    .bind(&target_bidx)
+    .fetch_optional(&mut tx)
+    .instrument(query_span(&sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?
+    .ok_or(CustomError::Conflict)?;
+
+    let sql = "UPDATE contact_verification SET confirmed_at=now() WHERE id=$1";
+    sqlx::query(sql)
+    .bind(verification_id)
+    .execute(&mut tx)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    cache.invalidate(tenant.id, id).await;
+
+    Ok(Json(Verified { channel, verified_at }))
+}
+
+// Every code issued for the employee, oldest first.
+pub async fn verifications(conn: &mut PgConnection, tenant: &Tenant, id: i32) -> Result<Vec<Verification>, CustomError> {
+    let sql = "SELECT id, channel, attempts, created_at, expires_at, confirmed_at FROM contact_verification WHERE employee_id=$1 AND tenant_id=$2 ORDER BY id";
+    sqlx::query_as(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_all(conn)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })
+}
@@ -1,82 +1,298 @@
-use axum::{response::IntoResponse, extract::Path, http::StatusCode, Extension, Json};
+use std::sync::Arc;
+
+use axum::{extract::{OriginalUri, Path, Query}, http::StatusCode, response::{IntoResponse, Response}, Extension, Json};
+use chrono::{DateTime, Utc};
 use serde_json::{json, Value};
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder};
+use tracing::Instrument;
 
 use crate::{
+    cache::ProfileCache,
+    custom_fields::{self, FieldKind},
     models::{*, self},
     errors::CustomError,
+    idempotency::Subjects,
+    logging::query_span,
+    merges,
+    negotiate::{Format, Negotiated, Payload},
+    pii::{email_domain, Pii, PiiAccess},
+    replicas::ReadPool,
+    tenants::Tenant,
 };
 
-pub async fn all_profiles(Extension(pool): Extension<PgPool>) -> impl IntoResponse {
-    let sql = "SELECT * FROM employee".to_string();
-    let profile = sqlx::query_as::<_, Profile>(&sql).fetch_all(&pool).await.unwrap();
+#[tracing::instrument(name = "views.all_profiles", skip_all)]
+pub async fn all_profiles(Query(filter): Query<ProfileFilter>, format: Format, tenant: Tenant, access: PiiAccess, Extension(pii): Extension<Arc<Pii>>, ReadPool(pool, _): ReadPool) -> Result<(StatusCode, Negotiated<Vec<models::Profile>>), CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+    let mut profile = find_profiles(&mut tx, &tenant, &pii, &filter).await?;
+
+    for p in &mut profile {
+        pii.reveal(p, &access)?;
+    }
 
-    (StatusCode::OK, Json(profile))
+    Ok((StatusCode::OK, Negotiated(format, profile)))
 }
 
-pub async fn profile(Path(id): Path<i32>, Extension(pool): Extension<PgPool>) -> Result<Json<models::Profile>, CustomError> {
-    let sql = "SELECT * FROM employee where id=$1".to_string();
-    let profile : models::Profile = sqlx::query_as(&sql).bind(id).fetch_one(&pool).await.map_err(|_| {
-        CustomError::TaskNotFound
-    })?;
+#[tracing::instrument(name = "views.profile", skip_all, fields(employee_id = id))]
+#[allow(clippy::too_many_arguments)]
+pub async fn profile(Path(id): Path<i32>, Query(at): Query<AsOf>, uri: OriginalUri, format: Format, tenant: Tenant, access: PiiAccess, Extension(pii): Extension<Arc<Pii>>, Extension(cache): Extension<Arc<ProfileCache>>, ReadPool(pool, source): ReadPool) -> Result<Response, CustomError> {
+    let cached = match at.as_of {
+        None if source.may_read_cache() => cache.get(tenant.id, id).await,
+        _ => None,
+    };
+
+    let mut profile = match (cached, at.as_of) {
+        (Some(profile), _) => profile,
+        (None, Some(as_of)) => {
+            let mut tx = tenant.begin(&pool).await?;
+            find_profile_as_of(&mut tx, &tenant, id, as_of).await?
+        }
+        (None, None) => {
+            let mut tx = tenant.begin(&pool).await?;
+            let profile = match find_profile(&mut tx, &tenant, id).await {
+                Err(CustomError::TaskNotFound) => {
+                    // Merged employees are found under the record they were merged into.
+                    let survivor = merges::survivor(&mut tx, &tenant, id).await?.ok_or(CustomError::TaskNotFound)?;
+                    return Ok(merges::redirect(&uri, survivor));
+                }
+                profile => profile?,
+            };
+            if source.may_fill_cache() {
+                cache.put(tenant.id, &profile).await;
+            }
+            profile
+        }
+    };
+
+    pii.reveal(&mut profile, &access)?;
 
-    Ok(Json(profile))
+    Ok(Negotiated(format, profile).into_response())
 }
 
+#[tracing::instrument(name = "views.post_profile", skip_all, fields(employee_id = data.id))]
 #[axum_macros::debug_handler]
-pub async fn post_profile(Extension(pool): Extension<PgPool>, Json(data): Json<NewProfile>) -> Result<(StatusCode, Json<models::NewProfile>), CustomError> {
-    let sql = "INSERT INTO employee (id, eid, ename, eemail, econtact) values ($1, $2, $3, $4, $5)".to_string();
+pub async fn post_profile(format: Format, tenant: Tenant, Extension(pii): Extension<Arc<Pii>>, Extension(pool): Extension<PgPool>, Payload(data): Payload<NewProfile>) -> Result<(StatusCode, Extension<Subjects>, Negotiated<models::NewProfile>), CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+    insert_profile(&mut tx, &tenant, &pii, &data).await?;
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+
+    Ok((StatusCode::CREATED, Extension(Subjects(vec![data.id])), Negotiated(format, data)))
+}
+
+#[tracing::instrument(name = "views.update_profile", skip_all, fields(employee_id = id))]
+pub async fn update_profile(Path(id): Path<i32>, format: Format, tenant: Tenant, Extension(pii): Extension<Arc<Pii>>, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>, Payload(data): Payload<NewProfile>) -> Result<(StatusCode, Negotiated<models::NewProfile>), CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+    let id = modify_profile(&mut tx, &tenant, &pii, id, &data).await?;
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    cache.invalidate(tenant.id, id).await;
+
+    Ok((StatusCode::OK, Negotiated(format, data)))
+}
+
+#[tracing::instrument(name = "views.delete_profile", skip_all, fields(employee_id = id))]
+pub async fn delete_profile(Path(id): Path<i32>, tenant: Tenant, Extension(cache): Extension<Arc<ProfileCache>>, Extension(pool): Extension<PgPool>) -> Result<(StatusCode, Json<Value>), CustomError> {
+    let mut tx = tenant.begin(&pool).await?;
+    let id = remove_profile(&mut tx, &tenant, id).await?;
+    tx.commit().await.map_err(|_| CustomError::InternalServerError)?;
+    cache.invalidate(tenant.id, id).await;
+
+    Ok((StatusCode::OK ,Json(json!({"msg": "Profile Deleted"}))))
+}
+
+// The queries below run on a caller-supplied connection so handlers, batch
+// requests and the command line share them inside their own transactions.
+
+// Turns a stored version in employee_history back into an employee row.
+pub const HISTORY_ROW: &str = "(jsonb_populate_record(NULL::employee, data)).*";
+
+pub async fn find_profiles(conn: &mut PgConnection, tenant: &Tenant, pii: &Pii, filter: &ProfileFilter) -> Result<Vec<Profile>, CustomError> {
+    let fields = custom_fields::fields(&mut *conn, tenant).await?;
+
+    // Current rows, or the versions in employee_history valid at `as_of`.
+    let (select, column): (String, fn(&str) -> String) = match filter.as_of {
+        None => ("SELECT * FROM employee WHERE tenant_id=".to_string(), |name| name.to_string()),
+        Some(_) => (format!("SELECT {HISTORY_ROW} FROM employee_history WHERE merged_from IS NULL AND tenant_id="), |name| match name {
+            "id" => "employee_id".to_string(),
+            "custom" => "(data->'custom')".to_string(),
+            _ => format!("(data->>'{name}')"),
+        }),
+    };
+
+    let mut query = QueryBuilder::<Postgres>::new(select);
+    query.push_bind(tenant.id);
+    if let Some(as_of) = filter.as_of {
+        query.push(" AND valid_from <= ").push_bind(as_of).push(" AND (valid_to IS NULL OR valid_to > ").push_bind(as_of).push(")");
+    }
+    if let Some(email) = &filter.eemail {
+        query.push(format!(" AND {}=", column("eemail_bidx"))).push_bind(pii.email_index(email));
+    }
+    if let Some(contact) = &filter.econtact {
+        query.push(format!(" AND {}=", column("econtact_bidx"))).push_bind(pii.contact_index(contact));
+    }
+    if let Some(status) = filter.status {
+        query.push(format!(" AND {}=", column("status"))).push_bind(status.as_str());
+    }
+    for (name, value) in filter.custom() {
+        let field = fields.iter().find(|field| field.name == name).ok_or(CustomError::BadRequest)?;
+        let value = field.coerce(&Value::String(value.to_string())).ok_or(CustomError::BadRequest)?;
+        query.push(format!(" AND {} @> ", column("custom"))).push_bind(sqlx::types::Json(json!({ name: value })));
+    }
+
+    query.push(" ORDER BY ");
+    if let Some(sort) = &filter.sort {
+        let (key, direction) = match sort.strip_prefix('-') {
+            Some(key) => (key, "DESC"),
+            None => (sort.as_str(), "ASC"),
+        };
+        match (key, key.strip_prefix("custom.")) {
+            ("id" | "eid" | "ename" | "status", _) => {
+                query.push(format!("{} {direction}, ", column(key)));
+            }
+            (_, Some(name)) => {
+                let field = fields.iter().find(|field| field.name == name).ok_or(CustomError::BadRequest)?;
+                let cast = match field.kind {
+                    FieldKind::Integer | FieldKind::Number => "::numeric",
+                    _ => "",
+                };
+                query.push(format!("({}->>", column("custom"))).push_bind(name.to_string()).push(format!("){cast} {direction} NULLS LAST, "));
+            }
+            _ => return Err(CustomError::BadRequest),
+        }
+    }
+    query.push(column("id"));
+
+    let sql = query.sql().to_string();
+    let profiles = query
+    .build_query_as::<Profile>()
+    .fetch_all(conn)
+    .instrument(query_span(&sql, None))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?;
+
+    Ok(profiles)
+}
+
+pub async fn find_profile(conn: &mut PgConnection, tenant: &Tenant, id: i32) -> Result<Profile, CustomError> {
+    let sql = "SELECT * FROM employee where id=$1 AND tenant_id=$2".to_string();
+    sqlx::query_as(&sql).bind(id).bind(tenant.id).fetch_one(conn).instrument(query_span(&sql, Some(id))).await.map_err(|_| {
+        CustomError::TaskNotFound
+    })
+}
+
+// The version of the row that was current at `as_of`; not found when the
+// employee did not exist then.
+pub async fn find_profile_as_of(conn: &mut PgConnection, tenant: &Tenant, id: i32, as_of: DateTime<Utc>) -> Result<Profile, CustomError> {
+    let sql = format!("SELECT {HISTORY_ROW} FROM employee_history WHERE employee_id=$1 AND tenant_id=$2 AND merged_from IS NULL AND valid_from <= $3 AND (valid_to IS NULL OR valid_to > $3)");
+    sqlx::query_as(&sql).bind(id).bind(tenant.id).bind(as_of).fetch_one(conn).instrument(query_span(&sql, Some(id))).await.map_err(|_| {
+        CustomError::TaskNotFound
+    })
+}
+
+pub async fn insert_profile(conn: &mut PgConnection, tenant: &Tenant, pii: &Pii, data: &NewProfile) -> Result<(), CustomError> {
+    let custom = custom_fields::validate(&mut *conn, tenant, &data.custom.clone().unwrap_or_default()).await?;
+
+    let sql = "INSERT INTO employee (id, eid, ename, eemail, econtact, eemail_bidx, econtact_bidx, eemail_domain, tenant_id, custom) values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)".to_string();
     let _  = sqlx::query(&sql)
-    .bind(&data.id)
+    .bind(data.id)
     .bind(&data.eid)
     .bind(&data.ename)
-    .bind(&data.eemail)
-    .bind(&data.econtact)
-    .execute(&pool)
-    .await.map_err(|_| {
-        CustomError::InternalServerError
+    .bind(pii.encrypt(&data.eemail))
+    .bind(pii.encrypt(&data.econtact))
+    .bind(pii.email_index(&data.eemail))
+    .bind(pii.contact_index(&data.econtact))
+    .bind(email_domain(&data.eemail))
+    .bind(tenant.id)
+    .bind(sqlx::types::Json(custom))
+    .execute(conn)
+    .instrument(query_span(&sql, Some(data.id)))
+    .await.map_err(|error| match error {
+        // The tenant already has an employee with this id.
+        sqlx::Error::Database(error) if error.code().as_deref() == Some("23505") => CustomError::Conflict,
+        _ => CustomError::InternalServerError,
     })?;
 
-    Ok((StatusCode::CREATED, Json(data)))
+    Ok(())
+}
+
+// The id an employee is stored under: its own, or the id of the record it was
+// merged into.
+pub async fn resolve_id(conn: &mut PgConnection, tenant: &Tenant, id: i32) -> Result<i32, CustomError> {
+    let sql = "SELECT id FROM employee WHERE id=$1 AND tenant_id=$2 \
+               UNION ALL SELECT survivor_id FROM employee_merge WHERE merged_id=$1 AND tenant_id=$2 LIMIT 1";
+    sqlx::query_scalar(sql)
+    .bind(id)
+    .bind(tenant.id)
+    .fetch_optional(conn)
+    .instrument(query_span(sql, Some(id)))
+    .await.map_err(|_| {
+        CustomError::InternalServerError
+    })?
+    .ok_or(CustomError::TaskNotFound)
 }
 
-pub async fn update_profile(Path(id): Path<i32>, Extension(pool): Extension<PgPool>, Json(data): Json<NewProfile>) -> Result<(StatusCode, Json<models::NewProfile>), CustomError> {
-    let sql = "SELECT * FROM employee where id=$1".to_string();
-    let _ : models::Profile = sqlx::query_as(&sql).bind(id).fetch_one(&pool).await.map_err(|_| {
+// Updates the employee, following a merged id to its survivor, and returns
+// the id that was updated.
+pub async fn modify_profile(conn: &mut PgConnection, tenant: &Tenant, pii: &Pii, id: i32, data: &NewProfile) -> Result<i32, CustomError> {
+    let id = resolve_id(&mut *conn, tenant, id).await?;
+    let sql = "SELECT * FROM employee where id=$1 AND tenant_id=$2 AND erased_at IS NULL".to_string();
+    let _ : models::Profile = sqlx::query_as(&sql).bind(id).bind(tenant.id).fetch_one(&mut *conn).instrument(query_span(&sql, Some(id))).await.map_err(|_| {
         CustomError::TaskNotFound
     })?;
+    // Without custom values the stored ones are kept as they are.
+    let custom = match &data.custom {
+        Some(values) => Some(custom_fields::validate(&mut *conn, tenant, values).await?),
+        None => None,
+    };
 
-    sqlx::query("UPDATE employee SET eid=$1, ename=$2, eemail=$3, econtact=$4 WHERE id=$5")
+    let sql = "UPDATE employee SET eid=$1, ename=$2, eemail=$3, econtact=$4, eemail_bidx=$5, econtact_bidx=$6, eemail_domain=$7, custom=COALESCE($8, custom), \
+               eemail_verified_at = CASE WHEN eemail_bidx IS DISTINCT FROM $5 THEN NULL ELSE eemail_verified_at END, \
+               econtact_verified_at = CASE WHEN econtact_bidx IS DISTINCT FROM $6 THEN NULL ELSE econtact_verified_at END \
+               WHERE id=$9 AND tenant_id=$10";
+    sqlx::query(sql)
     .bind(&data.eid)
     .bind(&data.ename)
-    .bind(&data.eemail)
-    .bind(&data.econtact)
+    .bind(pii.encrypt(&data.eemail))
+    .bind(pii.encrypt(&data.econtact))
+    .bind(pii.email_index(&data.eemail))
+    .bind(pii.contact_index(&data.econtact))
+    .bind(email_domain(&data.eemail))
+    .bind(custom.map(sqlx::types::Json))
     .bind(id)
-    .execute(&pool)
+    .bind(tenant.id)
+    .execute(conn)
+    .instrument(query_span(sql, Some(id)))
     .await.map_err(|_| {
         CustomError::InternalServerError
     })?;
 
-    Ok((StatusCode::OK, Json(data)))
+    Ok(id)
 }
 
-pub async fn delete_profile(Path(id): Path<i32>, Extension(pool): Extension<PgPool>) -> Result<(StatusCode, Json<Value>), CustomError> {
-    let sql = "SELECT * FROM employee where id=$1".to_string();
+// Deletes the employee, following a merged id to its survivor, and returns
+// the id that was deleted.
+pub async fn remove_profile(conn: &mut PgConnection, tenant: &Tenant, id: i32) -> Result<i32, CustomError> {
+    let id = resolve_id(&mut *conn, tenant, id).await?;
+    let sql = "SELECT * FROM employee where id=$1 AND tenant_id=$2".to_string();
     let _ : models::Profile = sqlx::query_as(&sql)
     .bind(id)
-    .fetch_one(&pool)
+    .bind(tenant.id)
+    .fetch_one(&mut *conn)
+    .instrument(query_span(&sql, Some(id)))
     .await
     .map_err(|_| {
         CustomError::TaskNotFound
     })?;
 
-    sqlx::query("DELETE FROM employee WHERE id=$1")
+    let sql = "DELETE FROM employee WHERE id=$1 AND tenant_id=$2";
+    sqlx::query(sql)
     .bind(id)
-    .execute(&pool)
+    .bind(tenant.id)
+    .execute(conn)
+    .instrument(query_span(sql, Some(id)))
     .await
     .map_err(|_| {
         CustomError::TaskNotFound
     })?;
 
-    Ok((StatusCode::OK ,Json(json!({"msg": "Profile Deleted"}))))
+    Ok(id)
 }
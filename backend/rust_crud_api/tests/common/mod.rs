@@ -0,0 +1,90 @@
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use rand::Rng;
+use rust_crud_api::{config::Config, tenants::Tenant, Services};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use tower::ServiceExt;
+
+// The settings the server would load from the environment and `.env`.
+pub fn config() -> Config {
+    Config::load().expect("test configuration")
+}
+
+// A pool on DATABASE_URL, or None when the database cannot be reached, so
+// tests that need Postgres are skipped instead of failing on machines
+// without one.
+pub async fn pool(config: &Config) -> Option<PgPool> {
+    match PgPoolOptions::new().max_connections(5).connect(&config.database_url).await {
+        Ok(pool) => Some(pool),
+        Err(error) => {
+            eprintln!("skipping: database unavailable ({error})");
+            None
+        }
+    }
+}
+
+// A new tenant with a random slug, so tests never see each other's rows.
+pub async fn tenant(pool: &PgPool, config: &Config) -> (String, Tenant) {
+    let slug = format!("test-{:08x}", rand::thread_rng().gen::<u32>());
+    sqlx::query("INSERT INTO tenant (slug, name) values ($1, $1)")
+        .bind(&slug)
+        .execute(pool)
+        .await
+        .expect("create tenant");
+
+    let tenant = Tenant::from_slug(pool, config, &slug).await.expect("load tenant");
+    (slug, tenant)
+}
+
+// The HTTP API as the server runs it, called in-process.
+pub fn app(config: Config, pool: &PgPool) -> Router {
+    let config = Arc::new(config);
+    let services = Services::new(&config, pool.clone()).expect("services");
+    rust_crud_api::app(config, pool.clone(), &services).expect("app")
+}
+
+// Stores a new API key and returns its id and secret.
+pub async fn api_key(pool: &PgPool, tenant: Option<&Tenant>, scopes: &[&str]) -> (i32, String) {
+    let secret = format!("ck_test{:016x}", rand::thread_rng().gen::<u64>());
+    let id = sqlx::query_scalar("INSERT INTO api_key (name, prefix, key_hash, scopes, tenant_id) values ('test', $1, $2, $3, $4) RETURNING id")
+        .bind(&secret[..11])
+        .bind(hex::encode(Sha256::digest(secret.as_bytes())))
+        .bind(scopes.iter().map(|scope| scope.to_string()).collect::<Vec<_>>())
+        .bind(tenant.map(|tenant| tenant.id))
+        .fetch_one(pool)
+        .await
+        .expect("create api key");
+    (id, secret)
+}
+
+// Sends a JSON request and returns the status and the decoded body (Null when
+// the body is empty).
+pub async fn send(app: &Router, method: &str, uri: &str, headers: &[(&str, &str)], body: Option<Value>) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let request = match body {
+        Some(body) => request.header("content-type", "application/json").body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+pub fn employee(id: i32, name: &str) -> Value {
+    json!({"id": id, "eid": format!("E{id}"), "ename": name, "eemail": format!("e{id}@example.com"), "econtact": "+1 202-555-0100"})
+}
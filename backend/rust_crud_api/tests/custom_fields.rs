@@ -0,0 +1,36 @@
+mod common;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use common::{api_key, employee, send};
+
+#[tokio::test]
+async fn updates_without_custom_values_keep_them() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let (_, tenant) = common::tenant(&pool, &config).await;
+    let (_, admin) = api_key(&pool, Some(&tenant), &["admin"]).await;
+    let app = common::app(config, &pool);
+
+    let (status, _) = send(&app, "POST", "/v1/admin/custom-fields", &[("x-api-key", &admin)], Some(json!({"name": "team", "kind": "string", "required": true}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let mut data = employee(1, "Ada");
+    data["custom"] = json!({"team": "core"});
+    let (status, _) = send(&app, "POST", "/v1/profile", &[("x-api-key", &admin)], Some(data)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = send(&app, "PUT", "/v1/profile/1", &[("x-api-key", &admin)], Some(employee(1, "Ada Lovelace"))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, "GET", "/v1/profile/1", &[("x-api-key", &admin)], None).await;
+    assert_eq!(body["ename"], "Ada Lovelace");
+    assert_eq!(body["custom"], json!({"team": "core"}));
+
+    // Erasure clears them with the rest of the personal data.
+    let (status, _) = send(&app, "POST", "/v1/profile/1/erasure", &[("x-api-key", &admin)], Some(json!({"reason": "request"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, "GET", "/v1/profile/1", &[("x-api-key", &admin)], None).await;
+    assert_eq!(body["custom"], json!({}));
+    assert_eq!(body["eid"], "Erased");
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use common::{api_key, employee, send};
+
+#[tokio::test]
+async fn erasure_drops_verification_codes() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let (_, tenant) = common::tenant(&pool, &config).await;
+    let (_, admin) = api_key(&pool, Some(&tenant), &["admin"]).await;
+    let app = common::app(config, &pool);
+
+    send(&app, "POST", "/v1/profile", &[("x-api-key", &admin)], Some(employee(1, "Ada"))).await;
+    let (status, _) = send(&app, "POST", "/v1/profile/1/verify/email", &[("x-api-key", &admin)], None).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let (status, _) = send(&app, "POST", "/v1/profile/1/erasure", &[("x-api-key", &admin)], Some(json!({"reason": "request"}))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let codes: i64 = sqlx::query_scalar("SELECT count(*) FROM contact_verification WHERE tenant_id=$1")
+        .bind(tenant.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(codes, 0);
+}
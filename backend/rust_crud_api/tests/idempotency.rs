@@ -0,0 +1,40 @@
+mod common;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use common::{api_key, employee, send};
+
+#[tokio::test]
+async fn stored_responses_are_encrypted_and_erased_with_the_employee() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let (_, tenant) = common::tenant(&pool, &config).await;
+    let (_, admin) = api_key(&pool, Some(&tenant), &["admin"]).await;
+    let app = common::app(config, &pool);
+
+    let headers = [("x-api-key", admin.as_str()), ("idempotency-key", "create-ada")];
+    let (status, first) = send(&app, "POST", "/v1/profile", &headers, Some(employee(1, "Ada"))).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (body, employee_ids): (String, Vec<i32>) = sqlx::query_as("SELECT body, employee_ids FROM idempotency_key WHERE tenant_id=$1 AND key='create-ada'")
+        .bind(tenant.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(body.starts_with("enc:") && !body.contains(first["eemail"].as_str().unwrap()));
+    assert_eq!(employee_ids, vec![1]);
+
+    let (status, replayed) = send(&app, "POST", "/v1/profile", &headers, Some(employee(1, "Ada"))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(replayed, first);
+
+    let (status, _) = send(&app, "POST", "/v1/profile/1/erasure", &[("x-api-key", &admin)], Some(json!({"reason": "request"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    let stored: i64 = sqlx::query_scalar("SELECT count(*) FROM idempotency_key WHERE tenant_id=$1")
+        .bind(tenant.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, 0);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use common::{api_key, employee, send};
+
+#[tokio::test]
+async fn merged_records_move_to_the_survivor_and_redirect() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let (_, tenant) = common::tenant(&pool, &config).await;
+    let (_, admin) = api_key(&pool, Some(&tenant), &["admin"]).await;
+    let (_, reader) = api_key(&pool, Some(&tenant), &["pii:read"]).await;
+    let app = common::app(config, &pool);
+    let admin = [("x-api-key", admin.as_str())];
+    let reader = [("x-api-key", reader.as_str())];
+
+    send(&app, "POST", "/v1/profile", &admin, Some(employee(1, "Ada Lovelace"))).await;
+    let idempotent = [admin[0], ("idempotency-key", "create-duplicate")];
+    send(&app, "POST", "/v1/profile", &idempotent, Some(employee(2, "Ada Lovelace"))).await;
+    let (status, _) = send(&app, "POST", "/v1/profile/2/verify/email", &admin, None).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let (status, _) = send(&app, "POST", "/v1/profile/2/transitions", &admin, Some(json!({"to": "active", "reason": "started"}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, candidates) = send(&app, "GET", "/v1/profiles/duplicates", &admin, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((candidates[0]["id"].clone(), candidates[0]["duplicate_id"].clone()), (json!(1), json!(2)));
+
+    // Cached before the merge, so a stale entry would still answer.
+    let (status, _) = send(&app, "GET", "/v1/profile/2", &reader, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, "POST", "/v1/profile/1/merge", &reader, Some(json!({"duplicate_id": 2}))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(&app, "POST", "/v1/profile/1/merge", &admin, Some(json!({"duplicate_id": 2}))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, "GET", "/v1/profile/2", &reader, None).await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+
+    let (status, transitions) = send(&app, "GET", "/v1/profile/2/transitions", &admin, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(transitions[0]["employee_id"], json!(1));
+    assert_eq!(transitions[0]["merged_from"], json!(2));
+
+    let versions: i64 = sqlx::query_scalar("SELECT count(*) FROM employee_history WHERE employee_id=1 AND merged_from=2 AND tenant_id=$1")
+        .bind(tenant.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(versions > 0);
+
+    let codes: Vec<i32> = sqlx::query_scalar("SELECT employee_id FROM contact_verification WHERE tenant_id=$1")
+        .bind(tenant.id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(codes, vec![1]);
+
+    let responses: Vec<Vec<i32>> = sqlx::query_scalar("SELECT employee_ids FROM idempotency_key WHERE tenant_id=$1")
+        .bind(tenant.id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(responses, vec![vec![1]]);
+
+    // Writes to the merged id land on the survivor.
+    let (status, _) = send(&app, "PUT", "/v1/profile/2", &admin, Some(employee(2, "Ada King"))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, survivor) = send(&app, "GET", "/v1/profile/1", &reader, None).await;
+    assert_eq!(survivor["ename"], json!("Ada King"));
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use rust_crud_api::{
+    errors::CustomError,
+    pii::{Pii, PiiAccess},
+    seed::{self, Generator},
+    views,
+};
+
+#[test]
+fn same_seed_generates_same_profiles() {
+    let mut first = Generator::new(42);
+    let mut second = Generator::new(42);
+
+    for id in 1..=50 {
+        let (a, b) = (first.profile(id, &[]), second.profile(id, &[]));
+        assert_eq!((a.eid, a.ename, a.eemail, a.econtact), (b.eid, b.ename, b.eemail, b.econtact));
+    }
+}
+
+#[test]
+fn different_seeds_generate_different_profiles() {
+    let first: Vec<_> = (1..=20).map(|id| Generator::new(1).profile(id, &[]).eemail).collect();
+    let second: Vec<_> = (1..=20).map(|id| Generator::new(2).profile(id, &[]).eemail).collect();
+
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn same_seed_stores_same_rows() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let pii = Pii::from_config(&config).unwrap();
+
+    let mut rows = Vec::new();
+    for _ in 0..2 {
+        let (_, tenant) = common::tenant(&pool, &config).await;
+        let ids = seed::seed(&pool, &tenant, &pii, 7, 30).await.unwrap();
+        assert_eq!(ids, 1..=30);
+
+        let mut tx = tenant.begin(&pool).await.unwrap();
+        let mut profiles = Vec::new();
+        for id in ids {
+            let mut profile = views::find_profile(&mut tx, &tenant, id).await.unwrap();
+            pii.reveal(&mut profile, &PiiAccess(true)).unwrap();
+            profiles.push((profile.id, profile.eid, profile.ename, profile.eemail, profile.econtact));
+        }
+        rows.push(profiles);
+    }
+
+    let mut generator = Generator::new(7);
+    let expected: Vec<_> = (1..=30)
+        .map(|id| generator.profile(id, &[]))
+        .map(|p| (p.id, p.eid, p.ename, p.eemail, p.econtact))
+        .collect();
+    assert_eq!(rows[0], expected);
+    assert_eq!(rows[1], expected);
+}
+
+#[tokio::test]
+async fn seeding_nothing_inserts_nothing() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let pii = Pii::from_config(&config).unwrap();
+    let (_, tenant) = common::tenant(&pool, &config).await;
+
+    let ids = seed::seed(&pool, &tenant, &pii, 1, 0).await.unwrap();
+    assert!(ids.is_empty());
+}
+
+#[tokio::test]
+async fn seeding_fills_custom_fields() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let pii = Pii::from_config(&config).unwrap();
+    let (_, tenant) = common::tenant(&pool, &config).await;
+
+    let fields = [
+        ("team", "string", None, Some(3.0), Some(8.0), None),
+        ("code", "string", Some("[A-Za-z']+"), None, None, None),
+        ("level", "integer", None, Some(1.0), Some(5.0), None),
+        ("score", "number", None, None, None, None),
+        ("remote", "boolean", None, None, None, None),
+        ("started", "date", None, None, None, None),
+        ("grade", "enum", None, None, None, Some(vec!["junior".to_string(), "senior".to_string()])),
+    ];
+    for (name, kind, pattern, min, max, options) in fields.clone() {
+        sqlx::query("INSERT INTO custom_field (tenant_id, name, kind, required, pattern, min, max, options) values ($1, $2, $3, true, $4, $5, $6, $7)")
+            .bind(tenant.id)
+            .bind(name)
+            .bind(kind)
+            .bind(pattern)
+            .bind(min)
+            .bind(max)
+            .bind(options)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    let ids = seed::seed(&pool, &tenant, &pii, 3, 20).await.unwrap();
+
+    let mut tx = tenant.begin(&pool).await.unwrap();
+    for id in ids {
+        let profile = views::find_profile(&mut tx, &tenant, id).await.unwrap();
+        assert!(fields.iter().all(|(name, ..)| profile.custom.contains_key(*name)));
+    }
+}
+
+#[tokio::test]
+async fn seeding_past_the_largest_id_is_refused() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let pii = Pii::from_config(&config).unwrap();
+    let (_, tenant) = common::tenant(&pool, &config).await;
+
+    let mut generator = Generator::new(1);
+    let mut tx = tenant.begin(&pool).await.unwrap();
+    views::insert_profile(&mut tx, &tenant, &pii, &generator.profile(i32::MAX - 1, &[])).await.unwrap();
+    tx.commit().await.unwrap();
+
+    assert!(matches!(seed::seed(&pool, &tenant, &pii, 1, 5).await, Err(CustomError::BadRequest)));
+    assert_eq!(seed::seed(&pool, &tenant, &pii, 1, 1).await.unwrap(), i32::MAX..=i32::MAX);
+}
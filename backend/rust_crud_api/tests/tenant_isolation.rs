@@ -0,0 +1,161 @@
+mod common;
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use common::{api_key, employee, send};
+
+#[tokio::test]
+async fn reads_stay_within_the_tenant() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let (slug_a, a) = common::tenant(&pool, &config).await;
+    let (_, b) = common::tenant(&pool, &config).await;
+    let (_, key_a) = api_key(&pool, Some(&a), &["pii:read"]).await;
+    let (_, key_b) = api_key(&pool, Some(&b), &["pii:read"]).await;
+    let app = common::app(config, &pool);
+
+    let (status, _) = send(&app, "POST", "/profile", &[("x-api-key", &key_a)], Some(employee(1, "Ada"))).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = send(&app, "GET", "/profile/1", &[("x-api-key", &key_a)], None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, "GET", "/profile/1", &[("x-api-key", &key_b)], None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = send(&app, "GET", "/v1/profiles", &[("x-api-key", &key_b)], None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    // Naming the other tenant does not switch to it.
+    let (status, _) = send(&app, "GET", "/profile/1", &[("x-api-key", &key_b), ("x-tenant-id", &slug_a)], None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn writes_stay_within_the_tenant() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let (_, a) = common::tenant(&pool, &config).await;
+    let (_, b) = common::tenant(&pool, &config).await;
+    let (_, key_a) = api_key(&pool, Some(&a), &["pii:read"]).await;
+    let (_, key_b) = api_key(&pool, Some(&b), &["pii:read"]).await;
+    let app = common::app(config, &pool);
+
+    send(&app, "POST", "/profile", &[("x-api-key", &key_a)], Some(employee(1, "Ada"))).await;
+
+    let (status, _) = send(&app, "PUT", "/profile/1", &[("x-api-key", &key_b)], Some(employee(1, "Mallory"))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, "DELETE", "/profile/1", &[("x-api-key", &key_b)], None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Ids are per tenant, so the other tenant can use the same one.
+    let (status, _) = send(&app, "POST", "/profile", &[("x-api-key", &key_b)], Some(employee(1, "Bob"))).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = send(&app, "POST", "/profile", &[("x-api-key", &key_a)], Some(employee(1, "Ada again"))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, body) = send(&app, "GET", "/profile/1", &[("x-api-key", &key_a)], None).await;
+    assert_eq!(body["ename"], "Ada");
+    let (_, body) = send(&app, "GET", "/profile/1", &[("x-api-key", &key_b)], None).await;
+    assert_eq!(body["ename"], "Bob");
+}
+
+#[tokio::test]
+async fn anonymous_callers_cannot_pick_a_tenant() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let (slug_a, a) = common::tenant(&pool, &config).await;
+    let (_, key_a) = api_key(&pool, Some(&a), &[]).await;
+    let app = common::app(config, &pool);
+
+    send(&app, "POST", "/profile", &[("x-api-key", &key_a)], Some(employee(1, "Ada"))).await;
+
+    let (status, _) = send(&app, "GET", "/profile/1", &[("x-tenant-id", &slug_a)], None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, "PUT", "/profile/1", &[("x-tenant-id", &slug_a)], Some(employee(1, "Mallory"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn tokens_act_on_their_claimed_tenant() {
+    let mut config = common::config();
+    config.jwt_secret = Some("test-secret".to_string());
+    let Some(pool) = common::pool(&config).await else { return };
+    let (slug_a, a) = common::tenant(&pool, &config).await;
+    let (slug_b, _) = common::tenant(&pool, &config).await;
+    let (_, key_a) = api_key(&pool, Some(&a), &[]).await;
+    let app = common::app(config, &pool);
+
+    send(&app, "POST", "/profile", &[("x-api-key", &key_a)], Some(employee(1, "Ada"))).await;
+
+    let bearer = format!("Bearer {}", token("test-secret", &json!({"sub": "u1", "tenant": slug_a, "exp": 4_000_000_000i64})));
+    let (status, _) = send(&app, "GET", "/profile/1", &[("authorization", &bearer)], None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, "GET", "/profile/1", &[("authorization", &bearer), ("x-tenant-id", &slug_b)], None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let forged = format!("Bearer {}", token("other-secret", &json!({"sub": "u1", "tenant": slug_a, "exp": 4_000_000_000i64})));
+    let (status, _) = send(&app, "GET", "/profile/1", &[("authorization", &forged)], None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn admin_endpoints_stay_within_the_tenant() {
+    let config = common::config();
+    let Some(pool) = common::pool(&config).await else { return };
+    let (slug_a, a) = common::tenant(&pool, &config).await;
+    let (slug_b, b) = common::tenant(&pool, &config).await;
+    let (_, admin_a) = api_key(&pool, Some(&a), &["admin"]).await;
+    let (key_b_id, key_b) = api_key(&pool, Some(&b), &[]).await;
+    let app = common::app(config, &pool);
+
+    send(&app, "POST", "/profile", &[("x-api-key", &key_b)], Some(employee(1, "Bob"))).await;
+
+    let (status, _) = send(&app, "GET", &format!("/v1/admin/tenants/{slug_b}/profiles"), &[("x-api-key", &admin_a)], None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(&app, "GET", &format!("/v1/admin/tenants/{slug_a}/profiles"), &[("x-api-key", &admin_a)], None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, "GET", "/v1/admin/tenants", &[("x-api-key", &admin_a)], None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(&app, "POST", "/v1/admin/tenants", &[("x-api-key", &admin_a)], Some(json!({"slug": "rogue", "name": "Rogue"}))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = send(&app, "GET", "/v1/admin/api-keys", &[("x-api-key", &admin_a)], None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.as_array().unwrap().iter().all(|key| key["tenant_id"] == a.id));
+
+    let (status, _) = send(&app, "POST", "/v1/admin/api-keys", &[("x-api-key", &admin_a)], Some(json!({"name": "x", "scopes": ["admin"], "tenant": slug_b}))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = send(&app, "POST", "/v1/admin/api-keys", &[("x-api-key", &admin_a)], Some(json!({"name": "x", "scopes": ["admin"]}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["tenant_id"], a.id);
+
+    let (status, _) = send(&app, "POST", &format!("/v1/admin/api-keys/{key_b_id}/rotate"), &[("x-api-key", &admin_a)], None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send(&app, "DELETE", &format!("/v1/admin/api-keys/{key_b_id}"), &[("x-api-key", &admin_a)], None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Tenant-scoped admin endpoints follow the key's tenant too.
+    let (status, _) = send(&app, "GET", "/v1/profile/1/export", &[("x-api-key", &admin_a), ("x-tenant-id", &slug_b)], None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(&app, "GET", "/v1/profile/1/export", &[("x-api-key", &admin_a)], None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// An HS256 token for the claims.
+fn token(secret: &str, claims: &Value) -> String {
+    let signed = format!("{}.{}", URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256"}"#), URL_SAFE_NO_PAD.encode(claims.to_string()));
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(signed.as_bytes());
+    format!("{signed}.{}", URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+}